@@ -12,36 +12,107 @@
 * limitations under the License.
 */
 
-use crate::types::{VariableRequest, SubscribeStream, GraphiteError};
+use crate::endpoints::{Endpoint, EndpointSet};
+use crate::subscriptions::{SubscribeStream, SubscriptionManager};
+use crate::types::{VariableRequest, GraphiteError, RequestMetrics, RequestObserver};
 
-use reqwest::{Client as HttpClient, ClientBuilder, Response};
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::{Client as HttpClient, ClientBuilder, RequestBuilder, Response, StatusCode};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::delay_for;
+
+// Defines how requests failed because of network errors or unavailable servers are retried
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    // Total number of attempts, 1 disables retries
+    pub max_attempts: u32,
+    // Delay before the first retry, every next delay is multiplied by `delay_grow_factor`
+    pub delay: Duration,
+    pub delay_grow_factor: f32,
+    pub max_delay: Duration,
+    // HTTP statuses which mean that the same request can succeed later
+    pub retryable_statuses: Vec<u16>,
+}
+
+impl RetryPolicy {
+    // Returns delay before the given retry (starting from 1)
+    pub fn retry_delay(&self, retry: u32) -> Duration {
+        let factor = self.delay_grow_factor.max(1.0).powi(retry.saturating_sub(1) as i32);
+        let delay = self.delay.as_millis() as f64 * factor as f64;
+        std::cmp::min(Duration::from_millis(delay as u64), self.max_delay)
+    }
+
+    pub fn is_retryable_status(&self, status: StatusCode) -> bool {
+        self.retryable_statuses.contains(&status.as_u16())
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            delay: Duration::from_millis(500),
+            delay_grow_factor: 2.0,
+            max_delay: Duration::from_secs(10),
+            retryable_statuses: vec![429, 502, 503, 504],
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct GqlClient {
     client_htpp: HttpClient,
-    graphql_host: String,
-    graphql_socket_host: String,
+    endpoints: EndpointSet,
+    retry_policy: RetryPolicy,
+    // clones share the subscriptions connection
+    subscriptions: SubscriptionManager,
+    observer: Option<Arc<RequestObserver>>,
     incremented_id: u64
 }
 
 impl GqlClient {
-    pub fn new(queries_server: &str, subscriptions_server: &str) -> Result<Self, GraphiteError> {
-        let client = ClientBuilder::new()  
+    pub fn new(endpoints: EndpointSet, access_key: Option<&str>) -> Result<Self, GraphiteError> {
+        // access key is sent with every HTTP request
+        let mut headers = HeaderMap::new();
+        if let Some(key) = access_key {
+            let value = HeaderValue::from_str(&format!("Bearer {}", key))
+                .map_err(|err| GraphiteError::new(format!("Invalid access key: {}", err)))?;
+            headers.insert(AUTHORIZATION, value);
+        }
+
+        let client = ClientBuilder::new()
+            .default_headers(headers)
             .build()
             .map_err(|err| GraphiteError::new(err.to_string()))?;
 
         Ok(Self {
             client_htpp: client,
-            graphql_host: queries_server.to_owned(),
-            graphql_socket_host: subscriptions_server.to_owned(),
+            subscriptions: SubscriptionManager::new(endpoints.clone(), access_key.map(|key| key.to_owned())),
+            endpoints,
+            retry_policy: RetryPolicy::default(),
+            observer: None,
             incremented_id: 0
         })
     }
 
-    async fn process_response(response: Response) -> Result<serde_json::Value, GraphiteError> {
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // Sets callback called after every HTTP request with its measurements
+    pub fn with_request_observer(mut self, observer: Arc<RequestObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    async fn process_response(response: Response, bytes_received: &mut usize)
+        -> Result<serde_json::Value, GraphiteError>
+    {
         match response.text().await {
             Ok(res_str) => {
+                *bytes_received = res_str.len();
                 if let Ok(value) = serde_json::from_str(res_str.as_str()) {
                     if let Some(error) = crate::types::try_extract_error(&value) {
                         return Err(error);
@@ -55,45 +126,182 @@ impl GqlClient {
             Err(err) => Err(GraphiteError::new(err.to_string().clone()))
         }
     }
-    
+
+    // Error for the response with failed status. Servers report the reason in GraphQL `errors`
+    // even with 5xx statuses, so they are returned if present
+    async fn status_error(endpoint: &Endpoint, response: Response) -> GraphiteError {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        serde_json::from_str(&body)
+            .ok()
+            .and_then(|value| crate::types::try_extract_error(&value))
+            .unwrap_or_else(|| GraphiteError::new(format!(
+                "Server {} responded with status {}", endpoint.queries_url, status)))
+    }
+
+    // Sends request to the first healthy endpoint. If the endpoint is unreachable
+    // it is marked as unhealthy and request is sent to the next one. If all endpoints
+    // failed with transient errors the whole round is retried according to the retry policy.
+    // Requests which must not be repeated once the server could receive them (`resendable`
+    // is false) are sent to another endpoint or retried only after connection errors
+    async fn send_with_failover<F>(&self, build_request: F, resendable: bool, retries: &mut u32)
+        -> Result<Response, GraphiteError>
+        where F: Fn(&Endpoint) -> RequestBuilder
+    {
+        let mut attempt = 1;
+        loop {
+            *retries = attempt - 1;
+            let (error, retryable) = match self.send_to_any(&build_request, resendable).await {
+                Ok(response) => return Ok(response),
+                Err(result) => result
+            };
+
+            if !retryable || attempt >= self.retry_policy.max_attempts {
+                // errors reported by the server are returned as is to keep their codes
+                if !error.errors().is_empty() {
+                    return Err(error);
+                }
+                return Err(GraphiteError::with_cause(
+                    format!("Can't send request (attempts: {}): {}", attempt, error.message()),
+                    error));
+            }
+
+            delay_for(self.retry_policy.retry_delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    // Tries all endpoints once. Returns the last error and whether it is worth retrying
+    async fn send_to_any<F>(&self, build_request: &F, resendable: bool) -> Result<Response, (GraphiteError, bool)>
+        where F: Fn(&Endpoint) -> RequestBuilder
+    {
+        let mut last_error = GraphiteError::new("No endpoints provided".to_owned());
+        let mut retryable = false;
+        for (index, endpoint) in self.endpoints.ordered() {
+            match build_request(&endpoint).send().await {
+                Ok(response) if !response.status().is_server_error()
+                    && !self.retry_policy.is_retryable_status(response.status()) =>
+                {
+                    self.endpoints.mark_healthy(index);
+                    return Ok(response);
+                },
+                Ok(response) => {
+                    let status = response.status();
+                    retryable |= self.retry_policy.is_retryable_status(status);
+                    last_error = Self::status_error(&endpoint, response).await;
+                    // the server could process the request before it failed
+                    if !resendable && status.is_server_error() {
+                        self.endpoints.mark_unhealthy(index);
+                        return Err((last_error, false));
+                    }
+                },
+                Err(err) => {
+                    // request which can't be built will fail the same way on retry
+                    retryable |= !err.is_builder();
+                    // errors other than connection ones can happen after the server received the request
+                    let received = !resendable && !err.is_builder() && !err.is_connect();
+                    last_error = GraphiteError::with_cause(
+                        format!("Can't send request to {}: {}", endpoint.queries_url, err), err);
+                    if received {
+                        self.endpoints.mark_unhealthy(index);
+                        return Err((last_error, false));
+                    }
+                }
+            }
+            self.endpoints.mark_unhealthy(index);
+        }
+
+        Err((last_error, retryable))
+    }
+
+    // Sends request and reads the response reporting request measurements to the observer
+    async fn send<F>(&self, operations: Vec<String>, bytes_sent: usize, resendable: bool, build_request: F)
+        -> Result<serde_json::Value, GraphiteError>
+        where F: Fn(&Endpoint) -> RequestBuilder
+    {
+        let start = Instant::now();
+        let mut retries = 0;
+        let mut bytes_received = 0;
+        let result = match self.send_with_failover(build_request, resendable, &mut retries).await {
+            Ok(response) => Self::process_response(response, &mut bytes_received).await,
+            Err(err) => Err(err)
+        };
+
+        if let Some(observer) = &self.observer {
+            observer(&RequestMetrics {
+                operations,
+                latency: start.elapsed(),
+                bytes_sent,
+                bytes_received,
+                retries,
+                succeeded: result.is_ok(),
+            });
+        }
+
+        result
+    }
+
     pub async fn query(&self, query: String) -> Result<serde_json::Value, GraphiteError> {
-        let request = format!("{}?query={}", self.graphql_host, query);
-        let response = self.client_htpp.get(&request)
-            .send()
+        let request = VariableRequest::new(query.clone(), None);
+        let operations = request.operation_name().into_iter().collect();
+        self.send(operations, query.len(), !request.is_mutation(), |endpoint|
+            self.client_htpp.get(&endpoint.queries_url).query(&[("query", &query)]))
             .await
-            .map_err(|err| 
-                GraphiteError::new(format!("Can't send request: {}", err)))?;
-        
-        Self::process_response(response).await
     }
 
-    pub async fn query_vars(&self, request: VariableRequest) -> Result<serde_json::Value, GraphiteError> {
-        let request = match request.get_variables() {
-            Some(vars) =>  format!("{{ \"query\": \"{}\", \"variables\": {} }}", request.get_query(), vars),
-            None =>  format!("{{ \"query\": \"{}\" }}", request.get_query())
-        };
+    fn request_body<T: serde::Serialize + ?Sized>(request: &T) -> Result<String, GraphiteError> {
+        serde_json::to_string(request)
+            .map_err(|err| GraphiteError::new(format!("Can't serialize request: {}", err)))
+    }
 
-        let mut headers = HeaderMap::new();
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    async fn post(&self, requests: &[VariableRequest], body: String) -> Result<serde_json::Value, GraphiteError> {
+        let operations = requests.iter().filter_map(|request| request.operation_name()).collect();
+        let resendable = !requests.iter().any(VariableRequest::is_mutation);
+        self.send(operations, body.len(), resendable, |endpoint| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-        let response = self.client_htpp.post(&self.graphql_host)
-            .headers(headers)
-            .body(request)
-            .send()
+            self.client_htpp.post(&endpoint.queries_url)
+                .headers(headers)
+                .body(body.clone())
+            })
             .await
-            .map_err(|err| 
-                GraphiteError::new(format!("Can't send request: {}", err)))?;
-
-        Self::process_response(response).await
-    }
-    
-    pub fn subscribe(&self, request: VariableRequest) -> Result<SubscribeStream, GraphiteError> {
-        Ok(SubscribeStream::new(
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .map_err(|err| GraphiteError::new(format!("Cannot get time: {}", err)))?
-                .subsec_nanos(),
-            request,
-            &self.graphql_socket_host)?)
-    }
-}
\ No newline at end of file
+    }
+
+    pub async fn query_vars(&self, request: VariableRequest) -> Result<serde_json::Value, GraphiteError> {
+        let body = Self::request_body(&request)?;
+        self.post(std::slice::from_ref(&request), body).await
+    }
+
+    // Sends several operations in one HTTP request. Results are returned in the same order
+    // as requests, each of them can fail independently
+    pub async fn query_vars_batch(&self, requests: &[VariableRequest])
+        -> Result<Vec<Result<serde_json::Value, GraphiteError>>, GraphiteError>
+    {
+        let value = self.post(requests, Self::request_body(requests)?).await?;
+        let results = value.as_array()
+            .ok_or_else(|| GraphiteError::new(format!("Invalid batch response: {}", value)))?;
+
+        if results.len() != requests.len() {
+            return Err(GraphiteError::new(format!(
+                "Invalid batch response: expected {} results, got {}", requests.len(), results.len())));
+        }
+
+        Ok(results
+            .iter()
+            .map(|result| match crate::types::try_extract_error(result) {
+                Some(error) => Err(error),
+                None => Ok(result.clone())
+            })
+            .collect())
+    }
+
+    // Starts subscription. All subscriptions of the client share one websocket connection
+    pub async fn subscribe(&self, request: VariableRequest) -> Result<SubscribeStream, GraphiteError> {
+        self.subscriptions.subscribe(request).await
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/test_client.rs"]
+mod tests;
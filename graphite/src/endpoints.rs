@@ -0,0 +1,87 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use crate::types::GraphiteError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Time during which unreachable endpoint is not used if there are healthy ones
+const UNHEALTHY_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoint {
+    pub queries_url: String,
+    pub subscriptions_url: String,
+}
+
+impl Endpoint {
+    pub fn new(queries_url: &str, subscriptions_url: &str) -> Self {
+        Self {
+            queries_url: queries_url.to_owned(),
+            subscriptions_url: subscriptions_url.to_owned(),
+        }
+    }
+}
+
+// List of equivalent endpoints with their health state.
+// Clones share the state, so a failure noticed by one request is seen by all others.
+#[derive(Clone)]
+pub struct EndpointSet {
+    endpoints: Arc<Vec<Endpoint>>,
+    unhealthy_until: Arc<Mutex<Vec<Option<Instant>>>>,
+}
+
+impl EndpointSet {
+    pub fn new(endpoints: Vec<Endpoint>) -> Result<Self, GraphiteError> {
+        if endpoints.is_empty() {
+            return Err(GraphiteError::new("No endpoints provided".to_owned()));
+        }
+
+        let health = vec![None; endpoints.len()];
+        Ok(Self {
+            endpoints: Arc::new(endpoints),
+            unhealthy_until: Arc::new(Mutex::new(health)),
+        })
+    }
+
+    // Returns endpoints in order they should be tried: healthy ones in configured order
+    // followed by unhealthy ones starting from the one which cooldown expires first
+    pub fn ordered(&self) -> Vec<(usize, Endpoint)> {
+        let now = Instant::now();
+        let health = self.unhealthy_until.lock().unwrap();
+
+        let mut healthy = Vec::new();
+        let mut unhealthy = Vec::new();
+        for (index, until) in health.iter().enumerate() {
+            match until {
+                Some(until) if *until > now => unhealthy.push((*until, index)),
+                _ => healthy.push(index)
+            }
+        }
+        unhealthy.sort();
+
+        healthy.into_iter()
+            .chain(unhealthy.into_iter().map(|(_, index)| index))
+            .map(|index| (index, self.endpoints[index].clone()))
+            .collect()
+    }
+
+    pub fn mark_unhealthy(&self, index: usize) {
+        self.unhealthy_until.lock().unwrap()[index] = Some(Instant::now() + UNHEALTHY_COOLDOWN);
+    }
+
+    pub fn mark_healthy(&self, index: usize) {
+        self.unhealthy_until.lock().unwrap()[index] = None;
+    }
+}
@@ -13,7 +13,9 @@
 */
 
 pub mod client;
+pub mod endpoints;
+pub mod subscriptions;
 pub mod types;
 
 extern crate futures;
-extern crate websocket;
\ No newline at end of file
+extern crate tokio_tungstenite;
\ No newline at end of file
@@ -0,0 +1,537 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+// Subscriptions over graphql-ws protocol. All subscriptions of a client share one websocket
+// connection: every subscription gets its own operation id and the connection task routes
+// server frames to the stream with the same id. The connection is closed when the last
+// subscription stream is dropped.
+
+use crate::endpoints::EndpointSet;
+use crate::types::{GraphQLError, GraphiteError, SubscriptionEvent, VariableRequest, try_extract_error};
+use futures::channel::mpsc;
+use futures::lock::Mutex;
+use futures::stream::Stream;
+use futures::task::{Context, Poll};
+use futures::{Future, SinkExt, StreamExt};
+use serde_derive::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::{delay_for, Delay, Instant};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::tungstenite::handshake::client::Request as WsRequest;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+type WsClient = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type EventSender = mpsc::UnboundedSender<Result<SubscriptionEvent, GraphiteError>>;
+
+const RECONNECT_INITIAL_DELAY_MS: u64 = 500;
+const RECONNECT_MAX_DELAY_MS: u64 = 30000;
+// Subscriptions fail if the lost connection is not restored during this time
+const RECONNECT_TIMEOUT_MS: u64 = 300000;
+// Time to wait for `connection_ack` after `connection_init` is sent
+const ACK_TIMEOUT_MS: u64 = 10000;
+// If the server sends `ka` frames and no frame was received during this time
+// the connection is considered dead
+const KEEP_ALIVE_TIMEOUT_MS: u64 = 60000;
+
+// Message of graphql-ws protocol
+#[derive(Debug, Serialize)]
+struct WsFrame<'a, P: serde::Serialize> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u32>,
+    #[serde(rename = "type")]
+    frame_type: &'a str,
+    payload: P,
+}
+
+impl<'a, P: serde::Serialize> WsFrame<'a, P> {
+    fn to_message(&self) -> WsMessage {
+        // serialization of the frame can't fail: it contains only strings and JSON values
+        WsMessage::Text(serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+// `connection_init` carries access key in its payload for access-controlled endpoints
+fn init_message(access_key: &Option<String>) -> WsMessage {
+    let payload = match access_key {
+        Some(key) => json!({ "accessKey": key }),
+        None => json!({})
+    };
+
+    WsFrame { id: None, frame_type: "connection_init", payload }.to_message()
+}
+
+fn start_message(id: u32, request: &VariableRequest) -> WsMessage {
+    WsFrame { id: Some(id), frame_type: "start", payload: request }.to_message()
+}
+
+fn stop_message(id: u32) -> WsMessage {
+    WsFrame { id: Some(id), frame_type: "stop", payload: json!({}) }.to_message()
+}
+
+// Error reported by `error` or `connection_error` frame. Payload is a single error
+// or a list of errors
+fn frame_error(frame: &Value) -> GraphiteError {
+    let payload = &frame["payload"];
+    let errors = match payload {
+        Value::Array(errors) => errors.iter().map(GraphQLError::from_value).collect(),
+        Value::Object(_) if payload.get("errors").is_some() =>
+            return try_extract_error(frame)
+                .unwrap_or_else(|| GraphiteError::new(format!("Subscription error: {}", payload))),
+        payload => vec![GraphQLError::from_value(payload)]
+    };
+    GraphiteError::from_errors(errors)
+}
+
+// Operation id of the frame. Some servers send ids as strings
+fn frame_id(frame: &Value) -> Option<u32> {
+    match &frame["id"] {
+        Value::Number(id) => id.as_u64().map(|id| id as u32),
+        Value::String(id) => id.parse().ok(),
+        _ => None
+    }
+}
+
+// Connects to the first reachable endpoint. Unreachable endpoints are marked as unhealthy
+async fn connect_any(endpoints: EndpointSet, access_key: Option<String>) -> Result<WsClient, GraphiteError> {
+    let mut last_error = None;
+    for (index, endpoint) in endpoints.ordered() {
+        match connect(&endpoint.subscriptions_url, init_message(&access_key)).await {
+            Ok(client) => {
+                endpoints.mark_healthy(index);
+                return Ok(client);
+            },
+            Err(err) => {
+                endpoints.mark_unhealthy(index);
+                last_error = Some(err);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| GraphiteError::new("No endpoints provided".to_owned())))
+}
+
+// Opens websocket connection, initializes it and waits for acknowledgement
+async fn connect(host: &str, init: WsMessage) -> Result<WsClient, GraphiteError> {
+    let ws_request = WsRequest::builder()
+        .uri(host)
+        .header("Sec-WebSocket-Protocol", "graphql-ws")
+        .body(())
+        .map_err(|err|
+            GraphiteError::new(
+                format!("Can't create websocket client with address {}. Error {}", host, err)))?;
+
+    let (mut client, _) = connect_async(ws_request)
+        .await
+        .map_err(|err|
+            GraphiteError::new(
+                format!("Can't connect to websocket server {}. Error {}", host, err)))?;
+
+    client.send(init)
+        .await
+        .map_err(|err|
+            GraphiteError::new(
+                format!("Sending message across websocket failed. Error: {}", err)))?;
+
+    tokio::time::timeout(Duration::from_millis(ACK_TIMEOUT_MS), wait_ack(&mut client))
+        .await
+        .map_err(|_| GraphiteError::new(format!("Server {} did not acknowledge connection", host)))??;
+
+    Ok(client)
+}
+
+async fn wait_ack(client: &mut WsClient) -> Result<(), GraphiteError> {
+    while let Some(message) = client.next().await {
+        let text = match message {
+            Ok(WsMessage::Text(text)) => text,
+            Ok(WsMessage::Close(_)) => break,
+            Ok(_) => continue,
+            Err(err) => return Err(GraphiteError::with_cause(
+                format!("Websocket connection failed. Error: {}", err), err))
+        };
+
+        let frame: Value = serde_json::from_str(&text)
+            .map_err(|_| GraphiteError::new(format!("Invalid JSON: {}", text)))?;
+        match frame["type"].as_str() {
+            Some("connection_ack") => return Ok(()),
+            Some("connection_error") => return Err(frame_error(&frame)),
+            // `ka` can be sent before the acknowledgement
+            _ => continue
+        }
+    }
+
+    Err(GraphiteError::new("Connection closed before acknowledgement".to_owned()))
+}
+
+enum Command {
+    Start { id: u32, request: VariableRequest, sender: EventSender },
+    Stop { id: u32 },
+}
+
+// Handle of the connection task. The task stops when the last handle is dropped
+struct Connection {
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+// Opens subscriptions on the connection shared by all clones of the manager
+#[derive(Clone)]
+pub(crate) struct SubscriptionManager {
+    endpoints: EndpointSet,
+    access_key: Option<String>,
+    connection: Arc<Mutex<Weak<Connection>>>,
+    next_id: Arc<AtomicU32>,
+    keep_alive_timeout: Duration,
+    reconnect_timeout: Duration,
+}
+
+impl SubscriptionManager {
+    pub fn new(endpoints: EndpointSet, access_key: Option<String>) -> Self {
+        Self {
+            endpoints,
+            access_key,
+            connection: Arc::new(Mutex::new(Weak::new())),
+            next_id: Arc::new(AtomicU32::new(1)),
+            keep_alive_timeout: Duration::from_millis(KEEP_ALIVE_TIMEOUT_MS),
+            reconnect_timeout: Duration::from_millis(RECONNECT_TIMEOUT_MS),
+        }
+    }
+
+    pub async fn subscribe(&self, request: VariableRequest) -> Result<SubscribeStream, GraphiteError> {
+        let connection = self.connection().await?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::unbounded();
+
+        connection.commands
+            .unbounded_send(Command::Start { id, request, sender })
+            .map_err(|_| GraphiteError::new("Subscription connection is closed".to_owned()))?;
+
+        Ok(SubscribeStream { id, receiver, connection })
+    }
+
+    // Returns the connection used by existing subscriptions or opens the new one
+    async fn connection(&self) -> Result<Arc<Connection>, GraphiteError> {
+        // lock is held while connecting so concurrent subscriptions don't open several connections
+        let mut current = self.connection.lock().await;
+        if let Some(connection) = current.upgrade() {
+            // the task is finished if the server closed the connection or it can't be restored
+            if !connection.commands.is_closed() {
+                return Ok(connection);
+            }
+        }
+
+        let client = connect_any(self.endpoints.clone(), self.access_key.clone()).await?;
+        let (commands, receiver) = mpsc::unbounded();
+        tokio::spawn(ConnectionTask {
+            commands: receiver,
+            subscriptions: HashMap::new(),
+            endpoints: self.endpoints.clone(),
+            access_key: self.access_key.clone(),
+            state: ConnectionState::Connected(client),
+            outgoing: VecDeque::new(),
+            reconnect_delay: RECONNECT_INITIAL_DELAY_MS,
+            keep_alive: None,
+            keep_alive_timeout: self.keep_alive_timeout,
+            reconnect_timeout: self.reconnect_timeout,
+            disconnected_at: None,
+        });
+
+        let connection = Arc::new(Connection { commands });
+        *current = Arc::downgrade(&connection);
+        Ok(connection)
+    }
+}
+
+struct Subscription {
+    request: VariableRequest,
+    sender: EventSender,
+}
+
+enum ConnectionState {
+    Connected(WsClient),
+    Waiting(Delay),
+    Connecting(Pin<Box<dyn Future<Output=Result<WsClient, GraphiteError>> + Send>>),
+}
+
+// Serves all subscriptions of the connection: sends `start` and `stop` frames, routes
+// server frames to subscriptions and restores the connection when it is lost
+struct ConnectionTask {
+    commands: mpsc::UnboundedReceiver<Command>,
+    subscriptions: HashMap<u32, Subscription>,
+    endpoints: EndpointSet,
+    access_key: Option<String>,
+    state: ConnectionState,
+    // frames waiting for the socket to be ready
+    outgoing: VecDeque<WsMessage>,
+    reconnect_delay: u64,
+    // armed after the first `ka` frame, so servers without keep-alive are not affected
+    keep_alive: Option<Delay>,
+    keep_alive_timeout: Duration,
+    reconnect_timeout: Duration,
+    // time the connection was lost, reset when it is restored
+    disconnected_at: Option<Instant>,
+}
+
+impl ConnectionTask {
+    fn is_connected(&self) -> bool {
+        match self.state {
+            ConnectionState::Connected(_) => true,
+            _ => false
+        }
+    }
+
+    fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Start { id, request, sender } => {
+                // after reconnect all subscriptions are started anyway
+                if self.is_connected() {
+                    self.outgoing.push_back(start_message(id, &request));
+                }
+                self.subscriptions.insert(id, Subscription { request, sender });
+            },
+            Command::Stop { id } => {
+                if self.subscriptions.remove(&id).is_some() && self.is_connected() {
+                    self.outgoing.push_back(stop_message(id));
+                }
+            }
+        }
+    }
+
+    // Routes frame received from the server to the subscription it belongs to
+    fn process_frame(&mut self, text: &str) {
+        let frame: Value = match serde_json::from_str(text) {
+            Ok(frame) => frame,
+            Err(_) => {
+                let error = GraphiteError::new(format!("Invalid JSON: {}", text));
+                for subscription in self.subscriptions.values() {
+                    let _ = subscription.sender.unbounded_send(Err(error.clone()));
+                }
+                return;
+            }
+        };
+
+        // any frame proves the connection is alive
+        if let Some(keep_alive) = &mut self.keep_alive {
+            keep_alive.reset(Instant::now() + self.keep_alive_timeout);
+        }
+
+        let id = frame_id(&frame).unwrap_or_default();
+        match frame["type"].as_str() {
+            Some("data") => if let Some(subscription) = self.subscriptions.get(&id) {
+                let event = match try_extract_error(&frame) {
+                    Some(error) => Err(error),
+                    None => Ok(SubscriptionEvent::Data(frame))
+                };
+                let _ = subscription.sender.unbounded_send(event);
+            },
+            Some("ka") => if self.keep_alive.is_none() {
+                self.keep_alive = Some(delay_for(self.keep_alive_timeout));
+            },
+            // stream of the subscription is finished when its sender is dropped
+            Some("complete") => {
+                self.subscriptions.remove(&id);
+            },
+            Some("error") => if let Some(subscription) = self.subscriptions.remove(&id) {
+                let _ = subscription.sender.unbounded_send(Err(frame_error(&frame)));
+            },
+            Some("connection_error") => {
+                let error = frame_error(&frame);
+                for (_, subscription) in self.subscriptions.drain() {
+                    let _ = subscription.sender.unbounded_send(Err(error.clone()));
+                }
+            },
+            // `connection_ack` and unknown frames
+            _ => {}
+        }
+    }
+
+    fn on_connected(&mut self, client: WsClient) {
+        self.disconnected_at = None;
+        self.state = ConnectionState::Connected(client);
+        for (id, subscription) in &self.subscriptions {
+            self.outgoing.push_back(start_message(*id, &subscription.request));
+            let _ = subscription.sender.unbounded_send(Ok(SubscriptionEvent::Reconnected));
+        }
+    }
+
+    fn schedule_reconnect(&mut self) {
+        self.disconnected_at.get_or_insert_with(Instant::now);
+        self.keep_alive = None;
+        self.outgoing.clear();
+        self.state = ConnectionState::Waiting(delay_for(Duration::from_millis(self.reconnect_delay)));
+        self.reconnect_delay = std::cmp::min(self.reconnect_delay * 2, RECONNECT_MAX_DELAY_MS);
+    }
+
+    // Reports the error to all subscriptions and finishes their streams
+    fn fail(&mut self, error: GraphiteError) {
+        for (_, subscription) in self.subscriptions.drain() {
+            let _ = subscription.sender.unbounded_send(Err(error.clone()));
+        }
+    }
+
+    fn reconnect_expired(&self) -> bool {
+        self.disconnected_at
+            .map(|disconnected_at| disconnected_at.elapsed() >= self.reconnect_timeout)
+            .unwrap_or(false)
+    }
+
+    // Writes queued frames to the socket. Returns `false` if the socket is broken
+    fn send_outgoing(client: &mut WsClient, outgoing: &mut VecDeque<WsMessage>, cx: &mut Context<'_>) -> bool {
+        while !outgoing.is_empty() {
+            match client.poll_ready_unpin(cx) {
+                Poll::Ready(Ok(())) => {
+                    let message = outgoing.pop_front().unwrap();
+                    if client.start_send_unpin(message).is_err() {
+                        return false;
+                    }
+                },
+                Poll::Ready(Err(_)) => return false,
+                Poll::Pending => break
+            }
+        }
+
+        match client.poll_flush_unpin(cx) {
+            Poll::Ready(Err(_)) => false,
+            _ => true
+        }
+    }
+}
+
+impl Future for ConnectionTask {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+
+        loop {
+            match this.commands.poll_next_unpin(cx) {
+                Poll::Ready(Some(command)) => this.handle_command(command),
+                // the last subscription stream is dropped
+                Poll::Ready(None) => return Poll::Ready(()),
+                Poll::Pending => break
+            }
+        }
+
+        loop {
+            // every branch either changes the state or polls an inner future or stream
+            // which registers the waker, so returning `Pending` is safe here
+            match &mut this.state {
+                ConnectionState::Connected(client) => {
+                    if !Self::send_outgoing(client, &mut this.outgoing, cx) {
+                        this.schedule_reconnect();
+                        continue;
+                    }
+
+                    match client.poll_next_unpin(cx) {
+                        Poll::Ready(Some(Ok(WsMessage::Text(text)))) => {
+                            this.reconnect_delay = RECONNECT_INITIAL_DELAY_MS;
+                            this.process_frame(&text);
+                            continue;
+                        },
+                        // pings are answered by the websocket layer itself
+                        Poll::Ready(Some(Ok(WsMessage::Ping(_)))) | Poll::Ready(Some(Ok(WsMessage::Pong(_)))) |
+                        Poll::Ready(Some(Ok(WsMessage::Binary(_)))) => continue,
+                        // server finished the connection on purpose, so all streams are finished
+                        Poll::Ready(Some(Ok(WsMessage::Close(Some(frame))))) if frame.code == CloseCode::Normal => {
+                            this.subscriptions.clear();
+                            return Poll::Ready(());
+                        },
+                        // connection is lost
+                        Poll::Ready(Some(Ok(WsMessage::Close(_)))) | Poll::Ready(Some(Err(_))) | Poll::Ready(None) => {
+                            this.schedule_reconnect();
+                            continue;
+                        },
+                        Poll::Pending => {}
+                    }
+
+                    // keep-alive is checked only after all received frames are read: the task
+                    // isn't polled while the runtime is idle (e.g. basic scheduler between
+                    // `block_on` calls), so frames received meanwhile still prove the connection
+                    // is alive and the timeout is counted from the moment they are read
+                    let keep_alive_expired = this.keep_alive
+                        .as_mut()
+                        .map(|keep_alive| Pin::new(keep_alive).poll(cx).is_ready())
+                        .unwrap_or(false);
+                    if !keep_alive_expired {
+                        return Poll::Pending;
+                    }
+                    // no frames (including `ka`) were received in time, connection is dead
+                    this.schedule_reconnect();
+                },
+                ConnectionState::Waiting(delay) => {
+                    futures::ready!(Pin::new(delay).poll(cx));
+                    this.state = ConnectionState::Connecting(
+                        Box::pin(connect_any(this.endpoints.clone(), this.access_key.clone())));
+                },
+                ConnectionState::Connecting(future) => {
+                    match futures::ready!(future.as_mut().poll(cx)) {
+                        Ok(client) => this.on_connected(client),
+                        Err(err) if this.reconnect_expired() => {
+                            this.fail(GraphiteError::with_cause(
+                                format!("Subscription connection is not restored in {} ms. Error: {}",
+                                    this.reconnect_timeout.as_millis(), err),
+                                err));
+                            return Poll::Ready(());
+                        },
+                        Err(_) => this.schedule_reconnect()
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Stream of events of one subscription
+pub struct SubscribeStream {
+    id: u32,
+    receiver: mpsc::UnboundedReceiver<Result<SubscriptionEvent, GraphiteError>>,
+    connection: Arc<Connection>,
+}
+
+impl SubscribeStream {
+    // Stops the subscription. The stream ends after events already received are read
+    pub async fn unsubscribe(&mut self) -> Result<(), GraphiteError> {
+        self.connection.commands
+            .unbounded_send(Command::Stop { id: self.id })
+            .map_err(|_| GraphiteError::new("Subscription connection is closed".to_owned()))
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Drop for SubscribeStream {
+    fn drop(&mut self) {
+        // the connection task may be already finished if the socket is closed
+        let _ = self.connection.commands.unbounded_send(Command::Stop { id: self.id });
+    }
+}
+
+impl Stream for SubscribeStream {
+    type Item = Result<SubscriptionEvent, GraphiteError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/test_subscriptions.rs"]
+mod tests;
@@ -0,0 +1,324 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use super::*;
+use serde_json::json;
+use crate::endpoints::{Endpoint, EndpointSet};
+use crate::types::RequestMetrics;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const DATA_RESPONSE: &str = r#"{"data":{"messages":[]}}"#;
+const BATCH_RESPONSE: &str = r#"[{"data":{"messages":[{"id":"1"}]}},{"errors":[{"message":"Database is unavailable","extensions":{"code":"INTERNAL_SERVER_ERROR"}}]}]"#;
+const ERROR_RESPONSE: &str = r#"{"errors":[{"message":"Database is unavailable","extensions":{"code":"INTERNAL_SERVER_ERROR"}}]}"#;
+
+// Starts HTTP server answering requests with given statuses one by one (the last one is
+// repeated). Returns server address and counter of served requests
+async fn start_server(statuses: Vec<u16>) -> (String, Arc<AtomicUsize>) {
+    let responses = statuses
+        .into_iter()
+        .map(|status| (status, if status == 200 { DATA_RESPONSE } else { "" }))
+        .collect();
+    start_server_with_bodies(responses).await
+}
+
+// Same as `start_server` but responses have the given bodies
+async fn start_server_with_bodies(responses: Vec<(u16, &'static str)>) -> (String, Arc<AtomicUsize>) {
+    let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}/graphql", listener.local_addr().unwrap());
+    let served = Arc::new(AtomicUsize::new(0));
+    let counter = served.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(_) => return
+            };
+            let index = counter.fetch_add(1, Ordering::SeqCst);
+            let (status, body) = responses[std::cmp::min(index, responses.len() - 1)];
+
+            let mut request = vec![0u8; 4096];
+            let _ = socket.read(&mut request).await;
+
+            let response = format!(
+                "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status, body.len(), body);
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+
+    (address, served)
+}
+
+// Starts HTTP server answering with `DATA_RESPONSE` and keeping received requests
+async fn start_recording_server() -> (String, Arc<Mutex<Vec<String>>>) {
+    let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}/graphql", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let received = requests.clone();
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut request = vec![0u8; 4096];
+            let read = socket.read(&mut request).await.unwrap_or(0);
+            received.lock().unwrap().push(String::from_utf8_lossy(&request[..read]).to_string());
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                DATA_RESPONSE.len(), DATA_RESPONSE);
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+
+    (address, requests)
+}
+
+fn create_client(address: &str, max_attempts: u32) -> GqlClient {
+    create_failover_client(&[address], max_attempts)
+}
+
+fn create_failover_client(addresses: &[&str], max_attempts: u32) -> GqlClient {
+    let endpoints = addresses.iter().map(|address| Endpoint::new(address, address)).collect();
+    GqlClient::new(EndpointSet::new(endpoints).unwrap(), None)
+        .unwrap()
+        .with_retry_policy(RetryPolicy {
+            max_attempts,
+            delay: Duration::from_millis(10),
+            ..RetryPolicy::default()
+        })
+}
+
+// Values which break JSON or GraphQL when pasted into a request unescaped
+fn hostile_values() -> Vec<&'static str> {
+    vec![r#"""#, r#"\"#, r#"\""#, "\n\t\u{0}", r#"", "variables": {}}"#, "}]}", "  two  spaces  "]
+}
+
+fn request() -> VariableRequest {
+    VariableRequest::new("query messages { messages { id } }".to_owned(), None)
+}
+
+fn mutation() -> VariableRequest {
+    VariableRequest::new(
+        "mutation postRequests($requests:[Request]){postRequests(requests:$requests)}".to_owned(),
+        Some(json!({ "requests": [{ "id": "1", "body": "2" }] })))
+}
+
+// Address nothing listens on after the listener is dropped
+async fn dead_address() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("http://{}/graphql", listener.local_addr().unwrap())
+}
+
+// Indexes of endpoints in order they are tried
+fn endpoints_order(client: &GqlClient) -> Vec<usize> {
+    client.endpoints.ordered().into_iter().map(|(index, _)| index).collect()
+}
+
+#[test]
+fn test_retry_delay() {
+    let policy = RetryPolicy {
+        delay: Duration::from_millis(100),
+        delay_grow_factor: 3.0,
+        max_delay: Duration::from_millis(1000),
+        ..RetryPolicy::default()
+    };
+
+    assert_eq!(policy.retry_delay(1), Duration::from_millis(100));
+    assert_eq!(policy.retry_delay(2), Duration::from_millis(300));
+    assert_eq!(policy.retry_delay(3), Duration::from_millis(900));
+    assert_eq!(policy.retry_delay(4), Duration::from_millis(1000));
+}
+
+#[tokio::test]
+async fn test_retry_statuses() {
+    let (address, served) = start_server(vec![503, 502, 200]).await;
+    let result = create_client(&address, 3).query_vars(request()).await.unwrap();
+    assert_eq!(result, serde_json::from_str::<serde_json::Value>(DATA_RESPONSE).unwrap());
+    assert_eq!(served.load(Ordering::SeqCst), 3);
+
+    let (address, served) = start_server(vec![503]).await;
+    let err = create_client(&address, 2).query_vars(request()).await.unwrap_err();
+    assert!(err.message().contains("attempts: 2"));
+    assert!(err.message().contains("503"));
+    assert_eq!(served.load(Ordering::SeqCst), 2);
+
+    // not retryable server error
+    let (address, served) = start_server(vec![500, 200]).await;
+    create_client(&address, 3).query_vars(request()).await.unwrap_err();
+    assert_eq!(served.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_retry_network_error() {
+    let address = dead_address().await;
+
+    let err = create_client(&address, 2).query_vars(request()).await.unwrap_err();
+    assert!(err.message().contains("attempts: 2"));
+    assert!(std::error::Error::source(&err).is_some());
+}
+
+#[tokio::test]
+async fn test_request_observer() {
+    let metrics = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let observed = metrics.clone();
+    let observer = Arc::new(move |request: &RequestMetrics| observed.lock().unwrap().push(request.clone()));
+
+    let (address, _) = start_server(vec![503, 200]).await;
+    let client = create_client(&address, 3).with_request_observer(observer);
+    client.query_vars(request()).await.unwrap();
+    client.query_vars_batch(&[request(), request()]).await.unwrap_err();
+
+    let metrics = metrics.lock().unwrap();
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics[0].operations, vec!["messages".to_owned()]);
+    assert_eq!(metrics[0].retries, 1);
+    assert_eq!(metrics[0].bytes_sent, GqlClient::request_body(&request()).unwrap().len());
+    assert_eq!(metrics[0].bytes_received, DATA_RESPONSE.len());
+    assert!(metrics[0].latency >= Duration::from_millis(10));
+    assert!(metrics[0].succeeded);
+
+    // the server answers batch with a single result
+    assert_eq!(metrics[1].operations, vec!["messages".to_owned(), "messages".to_owned()]);
+    assert_eq!(metrics[1].retries, 0);
+    assert!(metrics[1].succeeded);
+}
+
+#[test]
+fn test_operation_name() {
+    let name = |query: &str| VariableRequest::new(query.to_owned(), None).operation_name();
+    assert_eq!(name("query messages($filter: MessageFilter) { messages { id } }"), Some("messages".to_owned()));
+    assert_eq!(name("mutation postRequests($requests: [Request]) { postRequests(requests: $requests) }"),
+        Some("postRequests".to_owned()));
+    assert_eq!(name("subscription blocks{ blocks { id } }"), Some("blocks".to_owned()));
+    assert_eq!(name("query { messages { id } }"), None);
+    assert_eq!(name("{ messages { id } }"), None);
+}
+
+#[test]
+fn test_request_body_escaping() {
+    for value in hostile_values() {
+        let variables = json!({ "filter": { "id": { "eq": value } } });
+        let request = VariableRequest::new(format!("query {{ messages {{ id }} }} # {}", value), Some(variables.clone()));
+
+        let body: serde_json::Value = serde_json::from_str(&GqlClient::request_body(&request).unwrap()).unwrap();
+        assert_eq!(body, json!({ "query": request.get_query(), "variables": variables }));
+
+        let batch: serde_json::Value = serde_json::from_str(
+            &GqlClient::request_body(&[request.clone(), request.clone()][..]).unwrap()).unwrap();
+        assert_eq!(batch, json!([body, body]));
+    }
+
+    let request = VariableRequest::new("query { messages { id } }".to_owned(), None);
+    assert_eq!(GqlClient::request_body(&request).unwrap(), r#"{"query":"query { messages { id } }"}"#);
+}
+
+#[tokio::test]
+async fn test_failover() {
+    let dead = dead_address().await;
+    let (address, served) = start_server(vec![200]).await;
+    let client = create_failover_client(&[&dead, &address], 1);
+
+    client.query_vars(request()).await.unwrap();
+    assert_eq!(served.load(Ordering::SeqCst), 1);
+    assert_eq!(endpoints_order(&client), vec![1, 0]);
+
+    // clones share the endpoints health, unreachable endpoint is not tried anymore
+    client.clone().query_vars(request()).await.unwrap();
+    assert_eq!(served.load(Ordering::SeqCst), 2);
+
+    // server error makes endpoint unhealthy as well
+    let (failing, failed) = start_server(vec![503]).await;
+    let (address, served) = start_server(vec![200]).await;
+    let client = create_failover_client(&[&failing, &address], 1);
+    client.query_vars(request()).await.unwrap();
+    assert_eq!((failed.load(Ordering::SeqCst), served.load(Ordering::SeqCst)), (1, 1));
+    assert_eq!(endpoints_order(&client), vec![1, 0]);
+
+    // endpoint answered successfully becomes healthy again
+    client.endpoints.mark_unhealthy(1);
+    assert_eq!(endpoints_order(&client), vec![0, 1]);
+    client.query_vars(request()).await.unwrap();
+    assert_eq!((failed.load(Ordering::SeqCst), served.load(Ordering::SeqCst)), (2, 2));
+    assert_eq!(endpoints_order(&client), vec![1, 0]);
+}
+
+#[tokio::test]
+async fn test_server_error_body() {
+    let (address, _) = start_server_with_bodies(vec![(500, ERROR_RESPONSE)]).await;
+    let err = create_client(&address, 3).query_vars(request()).await.unwrap_err();
+    assert_eq!(err.message(), "Database is unavailable");
+    assert_eq!(err.errors()[0].code(), Some("INTERNAL_SERVER_ERROR"));
+
+    let (address, served) = start_server_with_bodies(vec![(503, ERROR_RESPONSE)]).await;
+    let err = create_client(&address, 2).query_vars(request()).await.unwrap_err();
+    assert_eq!(err.errors()[0].code(), Some("INTERNAL_SERVER_ERROR"));
+    assert_eq!(served.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_access_key_header() {
+    let (address, requests) = start_recording_server().await;
+    let endpoints = || EndpointSet::new(vec![Endpoint::new(&address, &address)]).unwrap();
+    let has_key = |request: &String| request.to_lowercase().contains("\r\nauthorization: bearer secret\r\n");
+
+    let client = GqlClient::new(endpoints(), Some("secret")).unwrap();
+    client.query_vars(request()).await.unwrap();
+    client.query_vars(mutation()).await.unwrap();
+    // the server doesn't answer with batch results, only the request is checked
+    client.query_vars_batch(&[request(), mutation()]).await.unwrap_err();
+    assert_eq!(requests.lock().unwrap().len(), 3);
+    assert!(requests.lock().unwrap().iter().all(has_key));
+
+    GqlClient::new(endpoints(), None).unwrap().query_vars(request()).await.unwrap();
+    assert!(!has_key(&requests.lock().unwrap()[3]));
+}
+
+#[tokio::test]
+async fn test_batch_response() {
+    let (address, _) = start_server_with_bodies(vec![(200, BATCH_RESPONSE)]).await;
+    let results = create_client(&address, 1).query_vars_batch(&[request(), request()]).await.unwrap();
+    assert_eq!(results[0].as_ref().unwrap(), &json!({ "data": { "messages": [{ "id": "1" }] } }));
+    assert_eq!(results[1].as_ref().unwrap_err().errors()[0].code(), Some("INTERNAL_SERVER_ERROR"));
+
+    // results can't be matched with requests
+    let err = create_client(&address, 1).query_vars_batch(&[request()]).await.unwrap_err();
+    assert!(err.message().contains("expected 1 results, got 2"));
+    let (address, _) = start_server_with_bodies(vec![(200, DATA_RESPONSE)]).await;
+    assert!(create_client(&address, 1).query_vars_batch(&[request()]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_mutation_failover() {
+    // mutation could be processed by the server which failed afterwards, so it is not repeated
+    let (failing, failed) = start_server(vec![503]).await;
+    let (address, served) = start_server(vec![200]).await;
+    let err = create_failover_client(&[&failing, &address], 3).query_vars(mutation()).await.unwrap_err();
+    assert!(err.message().contains("503"));
+    assert_eq!((failed.load(Ordering::SeqCst), served.load(Ordering::SeqCst)), (1, 0));
+
+    let err = create_failover_client(&[&failing, &address], 3)
+        .query_vars_batch(&[request(), mutation()])
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("503"));
+    assert_eq!((failed.load(Ordering::SeqCst), served.load(Ordering::SeqCst)), (2, 0));
+
+    // unreachable server didn't receive the mutation
+    let dead = dead_address().await;
+    create_failover_client(&[&dead, &address], 1).query_vars(mutation()).await.unwrap();
+    assert_eq!(served.load(Ordering::SeqCst), 1);
+}
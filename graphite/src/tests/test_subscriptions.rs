@@ -0,0 +1,275 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use super::*;
+use crate::endpoints::Endpoint;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+// Starts graphql-ws server which acknowledges every connection and answers `start`
+// with the given frames marked with the operation id. Pseudo frame `close` closes
+// the connection normally, `shutdown` drops it and stops accepting connections and
+// `delay` pauses sending for `ms` milliseconds.
+// Returns endpoints and counter of accepted connections
+async fn start_server(frames: Vec<Value>) -> (EndpointSet, Arc<AtomicUsize>) {
+    let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("ws://{}/graphql", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = connections.clone();
+    let stopped = Arc::new(AtomicBool::new(false));
+
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            if stopped.load(Ordering::SeqCst) {
+                continue;
+            }
+            counter.fetch_add(1, Ordering::SeqCst);
+            let frames = frames.clone();
+            let stopped = stopped.clone();
+            tokio::spawn(async move {
+                let mut socket = tokio_tungstenite::accept_async(socket).await.unwrap();
+                while let Some(Ok(message)) = socket.next().await {
+                    let message = frame(message);
+                    let answer = match message["type"].as_str() {
+                        Some("connection_init") if message["payload"]["accessKey"] == "wrong" =>
+                            vec![json!({ "type": "connection_error", "payload": { "message": "Unauthorized" } })],
+                        Some("connection_init") => vec![json!({ "type": "ka" }), json!({ "type": "connection_ack" })],
+                        Some("start") => frames
+                            .iter()
+                            .map(|frame| {
+                                let mut frame = frame.clone();
+                                frame["id"] = message["id"].clone();
+                                frame
+                            })
+                            .collect(),
+                        _ => vec![]
+                    };
+                    for answer in answer {
+                        match answer["type"].as_str() {
+                            Some("close") => {
+                                let frame = CloseFrame { code: CloseCode::Normal, reason: "".into() };
+                                let _ = socket.close(Some(frame)).await;
+                            },
+                            Some("shutdown") => {
+                                stopped.store(true, Ordering::SeqCst);
+                                return;
+                            },
+                            Some("delay") => delay_for(Duration::from_millis(answer["ms"].as_u64().unwrap())).await,
+                            _ => {
+                                let _ = socket.send(WsMessage::Text(answer.to_string())).await;
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    (EndpointSet::new(vec![Endpoint::new(&address, &address)]).unwrap(), connections)
+}
+
+fn request() -> VariableRequest {
+    VariableRequest::new("subscription { messages { id } }".to_owned(), None)
+}
+
+fn data(id: &str) -> Value {
+    json!({ "type": "data", "payload": { "data": { "messages": { "id": id } } } })
+}
+
+fn frame(message: WsMessage) -> Value {
+    match message {
+        WsMessage::Text(text) => serde_json::from_str(&text).unwrap(),
+        message => panic!("Text frame expected: {:?}", message)
+    }
+}
+
+#[test]
+fn test_frames() {
+    let filter = json!({ "id": { "eq": "\"}}, \"type\": \"stop\" \\" } });
+    let request = VariableRequest::new("subscription { messages { id } }".to_owned(), Some(json!({ "filter": filter })));
+
+    assert_eq!(frame(start_message(7, &request)), json!({
+        "id": 7,
+        "type": "start",
+        "payload": { "query": "subscription { messages { id } }", "variables": { "filter": filter } }
+    }));
+
+    assert_eq!(frame(stop_message(7)), json!({ "id": 7, "type": "stop", "payload": {} }));
+
+    assert_eq!(
+        frame(init_message(&Some("key \"with\" quotes".to_owned()))),
+        json!({ "type": "connection_init", "payload": { "accessKey": "key \"with\" quotes" } }));
+    assert_eq!(
+        frame(init_message(&None)),
+        json!({ "type": "connection_init", "payload": {} }));
+}
+
+#[tokio::test]
+async fn test_subscription_lifecycle() {
+    let (endpoints, _) = start_server(vec![
+        json!({ "type": "ka" }),
+        data("1"),
+        json!({ "type": "unknown" }),
+        data("2"),
+        json!({ "type": "complete" }),
+    ]).await;
+
+    let events: Vec<Value> = SubscriptionManager::new(endpoints, Some("key".to_owned()))
+        .subscribe(request())
+        .await
+        .unwrap()
+        .map(|event| match event.unwrap() {
+            SubscriptionEvent::Data(value) => value["payload"]["data"]["messages"]["id"].clone(),
+            SubscriptionEvent::Reconnected => panic!("Unexpected reconnect")
+        })
+        .collect()
+        .await;
+
+    assert_eq!(events, vec![json!("1"), json!("2")]);
+}
+
+#[tokio::test]
+async fn test_subscription_errors() {
+    let (endpoints, _) = start_server(vec![
+        data("1"),
+        json!({ "type": "error", "payload": { "message": "Subscription failed", "extensions": { "code": "INTERNAL_SERVER_ERROR" } } }),
+        data("2"),
+    ]).await;
+
+    let mut stream = SubscriptionManager::new(endpoints.clone(), None).subscribe(request()).await.unwrap();
+    assert!(stream.next().await.unwrap().is_ok());
+    let err = stream.next().await.unwrap().unwrap_err();
+    assert_eq!(err.message(), "Subscription failed");
+    assert_eq!(err.errors()[0].code(), Some("INTERNAL_SERVER_ERROR"));
+    assert!(stream.next().await.is_none());
+
+    let err = SubscriptionManager::new(endpoints, Some("wrong".to_owned())).subscribe(request()).await.err().unwrap();
+    assert_eq!(err.message(), "Unauthorized");
+}
+
+#[tokio::test]
+async fn test_keep_alive_timeout() {
+    // server sends `ka` once and then stays silent
+    let (endpoints, connections) = start_server(vec![json!({ "type": "ka" })]).await;
+
+    let mut manager = SubscriptionManager::new(endpoints, None);
+    manager.keep_alive_timeout = Duration::from_millis(200);
+    let mut stream = manager.subscribe(request()).await.unwrap();
+
+    match stream.next().await.unwrap().unwrap() {
+        SubscriptionEvent::Reconnected => {},
+        event => panic!("Reconnected expected: {:?}", event)
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_multiplexing() {
+    let (endpoints, connections) = start_server(vec![data("1")]).await;
+    let manager = SubscriptionManager::new(endpoints, None);
+
+    // every stream receives only frames of its own operation
+    let mut first = manager.subscribe(request()).await.unwrap();
+    let mut second = manager.clone().subscribe(request()).await.unwrap();
+    assert_ne!(first.get_id(), second.get_id());
+    for stream in vec![&mut first, &mut second] {
+        match stream.next().await.unwrap().unwrap() {
+            SubscriptionEvent::Data(value) => assert_eq!(value["id"], json!(stream.get_id())),
+            event => panic!("Data expected: {:?}", event)
+        }
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    // connection stays open while at least one subscription is alive
+    drop(first);
+    let third = manager.subscribe(request()).await.unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    // the last subscription closes the connection
+    drop(second);
+    drop(third);
+    let _fourth = manager.subscribe(request()).await.unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_normal_close() {
+    let (endpoints, connections) = start_server(vec![data("1"), json!({ "type": "close" })]).await;
+    let manager = SubscriptionManager::new(endpoints, None);
+
+    // stream ends without reconnect
+    let events: Vec<_> = manager.subscribe(request()).await.unwrap().collect().await;
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_ok());
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    // the next subscription opens the new connection
+    let _stream = manager.subscribe(request()).await.unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_reconnect_timeout() {
+    let (endpoints, connections) = start_server(vec![json!({ "type": "shutdown" })]).await;
+
+    let mut manager = SubscriptionManager::new(endpoints, None);
+    manager.reconnect_timeout = Duration::from_millis(200);
+    let mut stream = manager.subscribe(request()).await.unwrap();
+
+    let err = stream.next().await.unwrap().unwrap_err();
+    assert!(err.message().starts_with("Subscription connection is not restored in 200 ms"), "{}", err);
+    assert!(stream.next().await.is_none());
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_keep_alive_while_not_polled() {
+    // server runs on its own runtime, so it keeps sending `ka` frames while the client
+    // runtime is idle
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut runtime = tokio::runtime::Builder::new().basic_scheduler().enable_all().build().unwrap();
+        runtime.block_on(async move {
+            let mut frames = vec![json!({ "type": "ka" })];
+            for _ in 0..5 {
+                frames.push(json!({ "type": "delay", "ms": 100 }));
+                frames.push(json!({ "type": "ka" }));
+            }
+            frames.push(data("1"));
+            let _ = sender.send(start_server(frames).await);
+            futures::future::pending::<()>().await;
+        });
+    });
+    let (endpoints, connections) = receiver.recv().unwrap();
+
+    let mut runtime = tokio::runtime::Builder::new().basic_scheduler().enable_all().build().unwrap();
+    let mut manager = SubscriptionManager::new(endpoints, None);
+    manager.keep_alive_timeout = Duration::from_millis(200);
+    let mut stream = runtime.block_on(async {
+        let stream = manager.subscribe(request()).await.unwrap();
+        // let the connection task send `start` and receive the first `ka`
+        delay_for(Duration::from_millis(50)).await;
+        stream
+    });
+
+    // connection task is not polled longer than keep-alive timeout
+    std::thread::sleep(Duration::from_millis(800));
+
+    match runtime.block_on(stream.next()).unwrap().unwrap() {
+        SubscriptionEvent::Data(value) => assert_eq!(value["payload"]["data"]["messages"]["id"], json!("1")),
+        event => panic!("Data expected: {:?}", event)
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
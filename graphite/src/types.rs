@@ -12,26 +12,79 @@
 * limitations under the License.
 */
 
-extern crate futures;
-extern crate websocket;
-
-use futures::task::{Poll, Context};
-use futures::stream::Stream;
-use std::fmt;
+use serde_derive::Serialize;
 use serde_json::Value;
-use websocket::{ClientBuilder, OwnedMessage};
-use websocket::client::sync::Client;
-use websocket::stream::sync::NetworkStream;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Single entry of the `errors` list returned by the GraphQL server
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphQLError {
+    pub message: String,
+    pub locations: Option<Value>,
+    pub path: Option<Value>,
+    pub extensions: Option<Value>,
+}
 
+impl GraphQLError {
+    pub fn from_value(value: &Value) -> Self {
+        let field = |name: &str| value.get(name).filter(|field| !field.is_null()).cloned();
+        Self {
+            message: value["message"].as_str()
+                .map(|message| message.to_owned())
+                .unwrap_or_else(|| value.to_string()),
+            locations: field("locations"),
+            path: field("path"),
+            extensions: field("extensions"),
+        }
+    }
+
+    // Error code set by the server in `extensions.code` (like `GRAPHQL_VALIDATION_FAILED`
+    // or `INTERNAL_SERVER_ERROR`)
+    pub fn code(&self) -> Option<&str> {
+        self.extensions.as_ref()?.get("code")?.as_str()
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct GraphiteError {
     message: String,
+    errors: Vec<GraphQLError>,
+    cause: Option<Arc<dyn std::error::Error + Send + Sync>>,
 }
 
 impl GraphiteError {
     pub fn new(message: String) -> Self {
-        Self { message: message }
+        Self { message: message, errors: Vec::new(), cause: None }
+    }
+
+    // Creates error keeping the underlying error available through `source`
+    pub fn with_cause<E>(message: String, cause: E) -> Self
+        where E: std::error::Error + Send + Sync + 'static
+    {
+        Self { message, errors: Vec::new(), cause: Some(Arc::new(cause)) }
+    }
+
+    // Creates error from the errors returned by the server. Message contains all the
+    // error messages
+    pub fn from_errors(errors: Vec<GraphQLError>) -> Self {
+        let message = errors
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect::<Vec<&str>>()
+            .join("; ");
+        Self { message, errors, cause: None }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    // Errors returned by the GraphQL server. Empty if the request failed before the server
+    // answered (e.g. network error or invalid response)
+    pub fn errors(&self) -> &[GraphQLError] {
+        &self.errors
     }
 }
 
@@ -41,15 +94,23 @@ impl fmt::Display for GraphiteError {
     }
 }
 
-impl std::error::Error for GraphiteError {}
+impl std::error::Error for GraphiteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.cause.as_ref().map(|cause| cause.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
 
+// GraphQL operation. Serialized as a body of HTTP request or as a payload of
+// graphql-ws `start` frame
+#[derive(Debug, Clone, Serialize)]
 pub struct VariableRequest {
     query: String,
-    variables: Option<String>
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variables: Option<Value>
 }
 
 impl VariableRequest {
-    pub fn new(query: String, variables: Option<String>) -> Self {
+    pub fn new(query: String, variables: Option<Value>) -> Self {
         Self {
             query, variables
         }
@@ -59,79 +120,55 @@ impl VariableRequest {
         self.query.clone()
     }
 
-    pub fn get_variables(&self) -> Option<String> {
+    pub fn get_variables(&self) -> Option<Value> {
         self.variables.clone()
     }
-}
 
-pub struct SubscribeStream {
-    id: u32,
-    request: VariableRequest,
-    client: Client<Box<dyn NetworkStream + Send>>
-}
-
-impl SubscribeStream {
-    pub fn new(id: u32, request: VariableRequest, host:&str) -> Result<Self, GraphiteError> {
-        let client = ClientBuilder::new(host)
-            .map_err(|err| 
-                GraphiteError::new(
-                    format!("Can't create websocket client with address {}. Error {}", host, err)))?
-            .add_protocol("graphql-ws")
-            .connect(None)
-            .map_err(|err|
-                GraphiteError::new(
-                    format!("Can't connect to websocket server {}. Error {}", host, err)))?;
-
-        let mut future = Self {
-            id: id,
-            request: request,
-            client
-        };
-
-        future.subscribe()?;
-        Ok(future)
-    }
-
-    pub fn subscribe(&mut self) -> Result<(), GraphiteError> {
-        let query = &self.request.get_query().clone();
-        let variables = &self.request.get_variables().clone();
-        let request: String;
-
-        if let Some(vars) = variables {
-            request = format!("{{\"id\":{}, \"type\": \"start\", \"payload\":{{ \"query\": \"{}\", \"variables\": {} }}}}", &self.id, &query, &vars);
-        } else {
-            request = format!("{{\"id\":{}, \"type\": \"start\", \"payload\":{{ \"query\": \"{}\" }}}}", &self.id, &query);
-        }
-
-        let msg = OwnedMessage::Text(request);
-        self.client.send_message(&msg)
-            .map_err(|err| 
-                GraphiteError::new(
-                    format!("Sending message across stdin channel failed. Error: {}", err)))?;
-
-        Ok(())
+    // Mutations change the server state, so they must not be repeated if they could be
+    // processed already
+    pub fn is_mutation(&self) -> bool {
+        self.query.trim_start().starts_with("mutation")
     }
 
-    pub fn unsubscribe(&mut self) -> Result<(), GraphiteError> {
-        let query = format!("{{\"id\":{}, \"type\": \"stop\", \"payload\":{{}}}}", &self.id);
-        let msg = OwnedMessage::Text(query.to_string());
-        self.client.send_message(&msg)
-            .map_err(|err| 
-                GraphiteError::new(
-                    format!("Sending message across stdin channel failed. Error: {}", err)))?;
-
-        Ok(())
+    // Name of the operation: `messages` for `query messages($filter: ...) { ... }`.
+    // None for anonymous operations
+    pub fn operation_name(&self) -> Option<String> {
+        let query = self.query.trim_start();
+        let keyword = ["query", "mutation", "subscription"]
+            .iter()
+            .find(|keyword| query.starts_with(*keyword))?;
+        let rest = &query[keyword.len()..];
+        let name: String = rest
+            .trim_start()
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+        if name.is_empty() { None } else { Some(name) }
     }
+}
 
-    pub fn get_id(&self) -> u32 {
-        self.id
-    }
+// Measurements of one HTTP request passed to the request observer of `GqlClient`
+#[derive(Debug, Clone)]
+pub struct RequestMetrics {
+    // Names of the operations sent in the request (several ones for a batch)
+    pub operations: Vec<String>,
+    // Time from the first attempt until the response is read, including retry delays
+    pub latency: Duration,
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    // Number of repeated attempts, 0 if the first attempt succeeded
+    pub retries: u32,
+    pub succeeded: bool,
 }
 
-impl Drop for SubscribeStream {
-    fn drop(&mut self) {
-        let _ = self.unsubscribe();
-    }
+pub type RequestObserver = dyn Fn(&RequestMetrics) + Send + Sync;
+
+// Item of subscription stream. `Reconnected` is emitted after the connection was lost
+// and restored, so some updates could be missed between the previous item and the next one.
+#[derive(Debug, Clone)]
+pub enum SubscriptionEvent {
+    Data(Value),
+    Reconnected,
 }
 
 pub fn try_extract_error(value: &Value) -> Option<GraphiteError> {
@@ -141,50 +178,10 @@ pub fn try_extract_error(value: &Value) -> Option<GraphiteError> {
         value.get("errors")
     };
     
-    if let Some(errors) = errors {
-        if let Some(errors) = errors.as_array() {
-            if errors.len() > 0 {
-                if let Some(error) = errors.get(0) {
-                    if let Some(message) = error.get("message") {
-                        if let Some(string) = message.as_str() {
-                            return Some(GraphiteError::new(string.to_string()))
-                        }
-                    }
-                }
-            }
-        }
+    let errors = errors?.as_array()?;
+    if errors.is_empty() {
+        return None;
     }
 
-    return None;
-}
-
-impl Stream for SubscribeStream {
-    type Item = Result<Value, GraphiteError>;
-
-    fn poll_next(self: std::pin::Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if let Some(result) = self.get_mut().client.incoming_messages().next() {
-            match result {
-                Ok(message) => {
-                    match message {
-                        OwnedMessage::Text(text) => {
-                            if let Ok(value) = serde_json::from_str(text.as_str()) {
-                                if let Some(error) = try_extract_error(&value) {
-                                    return Poll::Ready(Some(Err(error)));
-                                }
-                                Poll::Ready(Some(Ok(value)))
-                            } else {
-                                Poll::Ready(Some(Err(GraphiteError::new(format!(
-                                        "Invalid JSON: {}", text)))))
-                            }
-                        },
-                        _ => Poll::Pending
-                    }
-
-                } ,
-                Err(err) => Poll::Ready(Some(Err(GraphiteError::new(err.to_string())))),
-            }
-        } else {
-            Poll::Pending
-        }
-    }
+    Some(GraphiteError::from_errors(errors.iter().map(GraphQLError::from_value).collect()))
 }
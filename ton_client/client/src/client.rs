@@ -17,10 +17,14 @@ use crate::types::{ApiResult, ApiError};
 use super::{JsonResponse, InteropContext};
 use std::collections::HashMap;
 use std::sync::{Mutex, MutexGuard};
-use ton_sdk::NodeClient;
+use ton_sdk::{Clock, NodeClient, SystemClock};
 
 #[cfg(feature = "node_interaction")]
 use tokio::runtime::Runtime;
+#[cfg(feature = "node_interaction")]
+use ton_sdk::CancellationToken;
+#[cfg(feature = "node_interaction")]
+use futures::Future;
 
 fn create_handlers() -> DispatchTable {
     let mut handlers = DispatchTable::new();
@@ -59,6 +63,33 @@ impl ClientContext {
         self.client.as_ref().ok_or(ApiError::sdk_not_init())
     }
 
+    // Time source for message expiration and local runs: the client clock corrected
+    // by the server clock offset, or the system clock if the client is not set up
+    pub fn clock(&self) -> &dyn Clock {
+        match &self.client {
+            Some(client) => client,
+            None => &SystemClock
+        }
+    }
+
+    // Synchronizes the clock with the server before messages are signed, like it is done
+    // when the client sends messages itself. Inside of running operations the runtime is
+    // taken and the clock is expected to be synchronized by the operation start
+    #[cfg(feature = "node_interaction")]
+    pub fn sync_clock(&mut self) -> ApiResult<()> {
+        match (&self.client, &mut self.runtime) {
+            (Some(client), Some(runtime)) => runtime
+                .block_on(client.sync_clock_once())
+                .map_err(|err| crate::types::apierror_from_sdkerror(err, ApiError::clock_out_of_sync)),
+            _ => Ok(())
+        }
+    }
+
+    #[cfg(not(feature = "node_interaction"))]
+    pub fn sync_clock(&mut self) -> ApiResult<()> {
+        Ok(())
+    }
+
     #[cfg(feature = "node_interaction")]
     pub fn take_runtime(&mut self) -> ApiResult<Runtime> {
         self.runtime.take().ok_or(ApiError::sdk_not_init())
@@ -75,6 +106,34 @@ lazy_static! {
     static ref CLIENT: Mutex<Client> = Mutex::new(Client::new());
 }
 
+#[cfg(feature = "node_interaction")]
+lazy_static! {
+    // Tokens of running context operations. They are kept out of the `CLIENT` lock, which is
+    // held by the running request, so the `cancel` request can be served at the same time
+    static ref CANCELLATION_TOKENS: Mutex<HashMap<InteropContext, CancellationToken>> = Mutex::new(HashMap::new());
+}
+
+// Runs the request future so it can be aborted by the `cancel` request of the same context
+#[cfg(feature = "node_interaction")]
+pub(crate) async fn cancellable<F, R>(context: InteropContext, future: F) -> ApiResult<R>
+    where F: Future<Output = ApiResult<R>>
+{
+    let token = CANCELLATION_TOKENS.lock().unwrap()
+        .entry(context)
+        .or_insert_with(CancellationToken::new)
+        .clone();
+
+    token.run(future).await.unwrap_or_else(|_| Err(ApiError::operation_cancelled()))
+}
+
+// Aborts all running operations of the context. Operations started afterwards get a new token
+#[cfg(feature = "node_interaction")]
+pub(crate) fn cancel_operations(context: InteropContext) {
+    if let Some(token) = CANCELLATION_TOKENS.lock().unwrap().remove(&context) {
+        token.cancel();
+    }
+}
+
 impl Client {
     fn new() -> Self {
         Self {
@@ -114,6 +173,11 @@ impl Client {
         if self.contexts.len() == 1 {
             self.json_sync_request(handle, "uninit".to_owned(), "{}".to_owned());
         }
+        #[cfg(feature = "node_interaction")]
+        {
+            cancel_operations(handle);
+            crate::contracts::processing::release_context(handle);
+        }
         self.contexts.remove(&handle);
     }
 
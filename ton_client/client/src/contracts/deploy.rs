@@ -161,19 +161,21 @@ pub(crate) fn get_address(_context: &mut ClientContext, params: ParamsOfGetDeplo
 pub(crate) fn encode_message(context: &mut ClientContext, params: ParamsOfDeploy) -> ApiResult<ResultOfEncodeDeployMessage> {
     debug!("-> contracts.deploy.message({:?})", params.call_set.clone());
 
+    context.sync_clock()?;
     let keys = params.key_pair.decode()?;
     let workchain = params.workchain_id.unwrap_or(DEFAULT_WORKCHAIN);
 
     let contract_image = create_image(&params.call_set.abi, params.init_params.as_ref(), &params.image_base64, &keys.public)?;
     let account_id = contract_image.msg_address(workchain);
     debug!("image prepared with address: {}", account_encode(&account_id));
-    let msg = Contract::construct_deploy_message_json(
+    let msg = Contract::construct_deploy_message_json_with_clock(
         params.call_set.into(),
         contract_image,
         Some(&keys),
         workchain,
         Some(context.get_client()?.timeouts()),
-        params.try_index
+        params.try_index,
+        context.clock()
     ).map_err(|err| ApiError::contracts_create_deploy_message_failed(err))?;
 
     let (body, id) = serialize_message(msg.message)?;
@@ -247,16 +249,18 @@ pub(crate) fn get_deploy_data(_context: &mut ClientContext, params: ParamsOfGetD
 }
 
 pub(crate) fn encode_unsigned_message(context: &mut ClientContext, params: ParamsOfEncodeUnsignedDeployMessage) -> ApiResult<ResultOfEncodeUnsignedDeployMessage> {
+    context.sync_clock()?;
     let public = decode_public_key(&params.public_key_hex)?;
     let image = create_image(&params.call_set.abi, params.init_params.as_ref(), &params.image_base64, &public)?;
     let workchain = params.workchain_id.unwrap_or(DEFAULT_WORKCHAIN);
     let address_hex = account_encode(&image.msg_address(workchain));
-    let encoded = ton_sdk::Contract::get_deploy_message_bytes_for_signing(
+    let encoded = ton_sdk::Contract::get_deploy_message_bytes_for_signing_with_clock(
         params.call_set.into(),
         image,
         workchain,
         Some(context.get_client()?.timeouts()),
-        params.try_index
+        params.try_index,
+        context.clock()
     ).map_err(|err| ApiError::contracts_create_deploy_message_failed(err))?;
     Ok(ResultOfEncodeUnsignedDeployMessage {
         encoded: EncodedUnsignedMessage {
@@ -323,7 +327,8 @@ async fn check_deployed(context: &mut ClientContext, address: &ton_block::MsgAdd
         filter,
         result: "id".to_owned(),
         limit: None,
-        order: None
+        order: None,
+        deadline: None
     })
         .await?;
 
@@ -30,9 +30,12 @@ use ton_sdk::Contract;
 
 pub(crate) mod deploy;
 pub(crate) mod run;
+pub(crate) mod transfer;
 
 #[cfg(feature = "node_interaction")]
 pub(crate) mod load;
+#[cfg(feature = "node_interaction")]
+pub(crate) mod processing;
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -202,7 +205,7 @@ pub(crate) async fn send_message(context: &mut ClientContext, params: EncodedMes
     let client = context.get_client()?;
     client.send_message(&id, &msg)
         .await
-        .map_err(|err| ApiError::contracts_send_message_failed(err))
+        .map_err(|err| crate::types::apierror_from_sdkerror(err, ApiError::contracts_send_message_failed))
 }
 
 #[cfg(feature = "node_interaction")]
@@ -248,7 +251,7 @@ pub(crate) fn register(handlers: &mut DispatchTable) {
     handlers.spawn("contracts.load",
         |context: &mut crate::client::ClientContext, params: load::LoadParams| {
             let mut runtime = context.take_runtime()?;
-            let result = runtime.block_on(load::load(context, params));
+            let result = runtime.block_on(crate::client::cancellable(context.handle, load::load(context, params)));
             context.runtime = Some(runtime);
             result
         });
@@ -258,7 +261,7 @@ pub(crate) fn register(handlers: &mut DispatchTable) {
     handlers.spawn("contracts.deploy",
         |context: &mut crate::client::ClientContext, params: deploy::ParamsOfDeploy| {
             let mut runtime = context.take_runtime()?;
-            let result = runtime.block_on(deploy::deploy(context, params));
+            let result = runtime.block_on(crate::client::cancellable(context.handle, deploy::deploy(context, params)));
             context.runtime = Some(runtime);
             result
         });
@@ -277,7 +280,7 @@ pub(crate) fn register(handlers: &mut DispatchTable) {
     handlers.spawn("contracts.run",
         |context: &mut crate::client::ClientContext, params: run::ParamsOfRun| {
             let mut runtime = context.take_runtime()?;
-            let result = runtime.block_on(run::run(context, params));
+            let result = runtime.block_on(crate::client::cancellable(context.handle, run::run(context, params)));
             context.runtime = Some(runtime);
             result
         });
@@ -286,6 +289,8 @@ pub(crate) fn register(handlers: &mut DispatchTable) {
         run::encode_message);
     handlers.spawn("contracts.run.encode_unsigned_message",
         run::encode_unsigned_message);
+    handlers.spawn("contracts.run.internal.message",
+        run::encode_internal_message);
     handlers.spawn("contracts.run.output",
         run::decode_output);
     handlers.spawn("contracts.run.unknown.input",
@@ -303,6 +308,18 @@ pub(crate) fn register(handlers: &mut DispatchTable) {
     handlers.spawn("contracts.run.fee.msg",
         |context, params| run::local_run_msg(context, params, false));
 
+    // Transfer
+    #[cfg(feature = "node_interaction")]
+    handlers.spawn("contracts.transfer",
+        |context: &mut crate::client::ClientContext, params: transfer::ParamsOfTransfer| {
+            let mut runtime = context.take_runtime()?;
+            let result = runtime.block_on(crate::client::cancellable(context.handle, transfer::transfer(context, params)));
+            context.runtime = Some(runtime);
+            result
+        });
+    handlers.spawn("contracts.transfer.message",
+        transfer::encode_message);
+
     // Contracts
     handlers.spawn("contracts.encode_message_with_sign",
         encode_message_with_sign);
@@ -326,7 +343,7 @@ pub(crate) fn register(handlers: &mut DispatchTable) {
     handlers.spawn("contracts.send.message",
         |context: &mut crate::client::ClientContext, params: EncodedMessage| {
             let mut runtime = context.take_runtime()?;
-            let result = runtime.block_on(send_message(context, params));
+            let result = runtime.block_on(crate::client::cancellable(context.handle, send_message(context, params)));
             context.runtime = Some(runtime);
             result
         });
@@ -334,7 +351,35 @@ pub(crate) fn register(handlers: &mut DispatchTable) {
     handlers.spawn("contracts.process.message",
         |context: &mut crate::client::ClientContext, params: ParamsOfProcessMessage| {
             let mut runtime = context.take_runtime()?;
-            let result = runtime.block_on(process_message(context, params));
+            let result = runtime.block_on(crate::client::cancellable(context.handle, process_message(context, params)));
+            context.runtime = Some(runtime);
+            result
+        });
+    #[cfg(feature = "node_interaction")]
+    handlers.spawn("contracts.process.message.start",
+        processing::start);
+    #[cfg(feature = "node_interaction")]
+    handlers.spawn("contracts.run.start",
+        processing::start_run);
+    #[cfg(feature = "node_interaction")]
+    handlers.spawn("contracts.process.message.next",
+        |context: &mut crate::client::ClientContext, params: processing::ProcessingHandle| {
+            let mut runtime = context.take_runtime()?;
+            let result = runtime.block_on(crate::client::cancellable(context.handle, processing::next(context, params)));
+            context.runtime = Some(runtime);
+            result
+        });
+    #[cfg(feature = "node_interaction")]
+    handlers.spawn("contracts.process.message.stop",
+        processing::stop);
+    #[cfg(feature = "node_interaction")]
+    handlers.spawn("contracts.process.message.state",
+        processing::state);
+    #[cfg(feature = "node_interaction")]
+    handlers.spawn("contracts.process.message.resume",
+        |context: &mut crate::client::ClientContext, params: processing::ParamsOfResumeProcessing| {
+            let mut runtime = context.take_runtime()?;
+            let result = runtime.block_on(crate::client::cancellable(context.handle, processing::resume(context, params)));
             context.runtime = Some(runtime);
             result
         });
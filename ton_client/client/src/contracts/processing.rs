@@ -0,0 +1,272 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use crate::InteropContext;
+use crate::client::ClientContext;
+use crate::contracts::ParamsOfProcessMessage;
+use crate::contracts::run;
+use crate::types::{ApiResult, ApiError, base64_decode};
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use ton_sdk::{MessageProcessing, ProcessingEvent, ProcessingState};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ParamsOfResumeProcessing {
+    pub abi: Option<serde_json::Value>,
+    pub function_name: Option<String>,
+    pub state: ProcessingState,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ProcessingHandle {
+    pub handle: u32
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ResultOfProcessingNext {
+    pub event: serde_json::Value,
+    // Decoded output of the message transaction, set with `transactionFound` event
+    pub output: Option<serde_json::Value>,
+}
+
+struct Processing {
+    processing: MessageProcessing,
+    abi: Option<serde_json::Value>,
+    function_name: Option<String>,
+    // parameters to build the message again if it is expired, not set for messages
+    // encoded by the caller
+    run: Option<run::ParamsOfRun>,
+    // expired message is going to be rebuilt by the next step
+    retry_pending: bool,
+}
+
+lazy_static! {
+    // processings are keyed by context handle so they are released with the context
+    static ref PROCESSINGS: Mutex<HashMap<(InteropContext, u32), Processing>> = Mutex::new(HashMap::new());
+}
+
+fn event_to_json(event: &ProcessingEvent) -> serde_json::Value {
+    match event {
+        ProcessingEvent::MessageSent { message_id, expire, try_index } => json!({
+            "type": "messageSent",
+            "messageId": message_id,
+            "expire": expire,
+            "tryIndex": try_index,
+        }),
+        ProcessingEvent::ShardBlockFound { message_id, block_id } => json!({
+            "type": "shardBlockFound",
+            "messageId": message_id,
+            "blockId": block_id,
+        }),
+        ProcessingEvent::TransactionFound { message_id, transaction_id } => json!({
+            "type": "transactionFound",
+            "messageId": message_id,
+            "transactionId": transaction_id,
+        }),
+        ProcessingEvent::Expired { message_id, try_index } => json!({
+            "type": "expired",
+            "messageId": message_id,
+            "tryIndex": try_index,
+        }),
+        ProcessingEvent::Retrying { try_index } => json!({
+            "type": "retrying",
+            "tryIndex": try_index,
+        }),
+    }
+}
+
+// Starts processing of the message. The message is sent by the first `next` request.
+// The message encoded by the caller is not retried if it is expired
+pub(crate) fn start(context: &mut ClientContext, params: ParamsOfProcessMessage) -> ApiResult<ProcessingHandle> {
+    debug!("-> contracts.process.message.start({}, {})",
+        params.message.message_id,
+        params.message.expire.unwrap_or_default());
+
+    let message = base64_decode(&params.message.message_body_base64)?;
+    let processing = MessageProcessing::new(
+        params.message.message_id.into(),
+        message,
+        params.message.expire,
+        params.try_index.unwrap_or(0));
+
+    new_handle(context, Processing {
+        processing,
+        abi: params.abi,
+        function_name: params.function_name,
+        run: None,
+        retry_pending: false,
+    })
+}
+
+// Starts processing of the contract function call. If the message is expired it is built
+// again with a longer expiration time, up to `message_retries_count` times
+pub(crate) fn start_run(context: &mut ClientContext, params: run::ParamsOfRun) -> ApiResult<ProcessingHandle> {
+    debug!("-> contracts.run.start({}, {:?})", params.address, params.call_set);
+
+    let try_index = params.try_index.unwrap_or(0);
+    let processing = encode_processing(context, params.clone(), try_index)?;
+
+    new_handle(context, Processing {
+        processing,
+        abi: Some(params.call_set.abi.clone()),
+        function_name: Some(params.call_set.function_name.clone()),
+        run: Some(params),
+        retry_pending: false,
+    })
+}
+
+fn encode_processing(context: &mut ClientContext, mut params: run::ParamsOfRun, try_index: u8)
+    -> ApiResult<MessageProcessing>
+{
+    params.try_index = Some(try_index);
+    let message = run::encode_message(context, params)?;
+
+    Ok(MessageProcessing::new(
+        message.message_id.into(),
+        base64_decode(&message.message_body_base64)?,
+        message.expire,
+        try_index))
+}
+
+fn new_handle(context: &ClientContext, processing: Processing) -> ApiResult<ProcessingHandle> {
+    let mut rng = rand::rngs::OsRng::new()
+        .map_err(|err| ApiError::contracts_process_message_failed(err))?;
+    let handle = rng.next_u32();
+
+    add_handle(context.handle, handle, processing);
+
+    Ok(ProcessingHandle { handle })
+}
+
+// Returns the processing back to the handles when dropped, so it survives cancellation
+// of the `next` request
+struct ProcessingGuard {
+    context: InteropContext,
+    handle: u32,
+    processing: Option<Processing>,
+}
+
+impl Drop for ProcessingGuard {
+    fn drop(&mut self) {
+        if let Some(processing) = self.processing.take() {
+            add_handle(self.context, self.handle, processing);
+        }
+    }
+}
+
+// Performs the next processing step and returns its event. If the message of the contract
+// function call is expired, the next step builds it again and returns `retrying` event.
+// The handle is released when the processing is finished, i.e. the transaction is found
+// or the message is expired and can't be retried
+pub(crate) async fn next(context: &mut ClientContext, params: ProcessingHandle) -> ApiResult<ResultOfProcessingNext> {
+    let processing = extract_handle(context.handle, params.handle)
+        .ok_or(ApiError::contracts_process_message_failed("Invalid handle"))?;
+    let mut guard = ProcessingGuard { context: context.handle, handle: params.handle, processing: Some(processing) };
+
+    if let Some(processing) = guard.processing.as_mut() {
+        if processing.retry_pending {
+            let try_index = processing.processing.state().try_index + 1;
+            let run_params = processing.run.clone()
+                .ok_or(ApiError::contracts_process_message_failed("Message can not be retried"))?;
+            processing.processing = encode_processing(context, run_params, try_index)?;
+            processing.retry_pending = false;
+
+            return Ok(ResultOfProcessingNext {
+                event: event_to_json(&ProcessingEvent::Retrying { try_index }),
+                output: None,
+            });
+        }
+    }
+
+    let client = context.get_client()?;
+    let result = match guard.processing.as_mut() {
+        Some(processing) => processing.processing.next_event(client).await,
+        None => return Err(ApiError::contracts_process_message_failed("Invalid handle"))
+    };
+    if let (Ok(ProcessingEvent::Expired { try_index, .. }), Some(processing)) = (&result, guard.processing.as_mut()) {
+        processing.retry_pending = processing.run.is_some()
+            && *try_index < client.timeouts().message_retries_count;
+    }
+    let finished = guard.processing.as_ref()
+        .map(|processing| processing.processing.is_finished() && !processing.retry_pending)
+        .unwrap_or(true);
+    let processing = if finished { guard.processing.take() } else { None };
+    drop(guard);
+
+    let event = result
+        .map_err(|err| crate::types::apierror_from_sdkerror(err, ApiError::contracts_process_message_failed))?;
+
+    let output = match (&event, processing) {
+        (ProcessingEvent::TransactionFound { .. }, Some(mut processing)) => {
+            let transaction = processing.processing.take_transaction()
+                .ok_or(ApiError::contracts_process_message_failed("Transaction is not found"))?;
+            run::process_transaction(client, transaction, processing.abi, processing.function_name)
+                .await?
+                .output
+        },
+        _ => serde_json::Value::Null
+    };
+
+    Ok(ResultOfProcessingNext {
+        event: event_to_json(&event),
+        output: if output.is_null() { None } else { Some(output) },
+    })
+}
+
+// Returns the state of the processing. Should be saved after the message is sent to be able
+// to resume the processing by `contracts.process.message.resume`
+pub(crate) fn state(context: &mut ClientContext, params: ProcessingHandle) -> ApiResult<ProcessingState> {
+    PROCESSINGS.lock().unwrap()
+        .get(&(context.handle, params.handle))
+        .map(|processing| processing.processing.state())
+        .ok_or(ApiError::contracts_process_message_failed("Invalid handle"))
+}
+
+// Waits for the transaction of the message from the saved processing state. The message is
+// sent only if it wasn't sent before the state is saved
+pub(crate) async fn resume(context: &mut ClientContext, params: ParamsOfResumeProcessing) -> ApiResult<run::ResultOfRun> {
+    debug!("-> contracts.process.message.resume({}, {})",
+        params.state.message_id,
+        params.state.expire.unwrap_or_default());
+
+    let client = context.get_client()?;
+    let transaction = MessageProcessing::resume(params.state)
+        .run(client, None)
+        .await
+        .map_err(|err| crate::types::apierror_from_sdkerror(err, ApiError::contracts_process_message_failed))?;
+
+    run::process_transaction(client, transaction, params.abi, params.function_name).await
+}
+
+pub(crate) fn stop(context: &mut ClientContext, params: ProcessingHandle) -> ApiResult<()> {
+    let _processing = extract_handle(context.handle, params.handle)
+        .ok_or(ApiError::contracts_process_message_failed("Invalid handle"))?;
+
+    Ok(())
+}
+
+// Releases all processings of the context
+pub(crate) fn release_context(context: InteropContext) {
+    PROCESSINGS.lock().unwrap().retain(|(processing_context, _), _| *processing_context != context);
+}
+
+fn add_handle(context: InteropContext, handle: u32, processing: Processing) {
+    PROCESSINGS.lock().unwrap().insert((context, handle), processing);
+}
+
+fn extract_handle(context: InteropContext, handle: u32) -> Option<Processing> {
+    PROCESSINGS.lock().unwrap().remove(&(context, handle))
+}
@@ -12,7 +12,7 @@
 * limitations under the License.
 */
 
-use ton_sdk::{Contract, MessageType, AbiContract, FunctionCallSet};
+use ton_sdk::{Contract, ContractImage, MessageType, AbiContract, FunctionCallSet, InternalMessageParams, OtherCurrencyValue};
 use ton_sdk::json_abi::encode_function_call;
 use crate::crypto::keys::{KeyPair, account_decode};
 use crate::types::{ApiResult, ApiError, base64_decode};
@@ -61,7 +61,7 @@ impl Into<FunctionCallSet> for RunFunctionCallSet {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ParamsOfRun {
     pub address: String,
@@ -100,6 +100,27 @@ pub(crate) struct ParamsOfEncodeUnsignedRunMessage {
     pub try_index: Option<u8>,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OtherCurrency {
+    pub currency: u32,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ParamsOfEncodeInternalMessage {
+    pub address: String,
+    pub src_address: Option<String>,
+    // Decimal or `0x` prefixed hex number of grams
+    pub value: String,
+    pub other_currencies: Option<Vec<OtherCurrency>>,
+    // `true` if not set
+    pub bounce: Option<bool>,
+    pub image_base64: Option<String>,
+    pub call_set: Option<RunFunctionCallSet>,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ParamsOfDecodeRunOutput {
@@ -258,13 +279,14 @@ pub(crate) fn local_run(context: &mut ClientContext, params: ParamsOfLocalRun, t
 
     let key_pair = params.key_pair.map(|pair| pair.decode()).transpose()?;
 
-    let msg = Contract::construct_call_message_json(
+    let msg = Contract::construct_call_message_json_with_clock(
         address,
         params.call_set.clone().into(),
         false,
         key_pair.as_ref(),
         None,
-        None)
+        None,
+        context.clock())
         .map_err(|err| ApiError::contracts_create_run_message_failed(err))?;
 
     local_run_msg(
@@ -296,7 +318,7 @@ pub(crate) fn local_run_msg(context: &mut ClientContext, params: ParamsOfLocalRu
         None => {
             debug!("load contract");
             let mut runtime = context.take_runtime()?;
-            let result = runtime.block_on(load_contract(context, &address));
+            let result = runtime.block_on(crate::client::cancellable(context.handle, load_contract(context, &address)));
             context.runtime = Some(runtime);
             result?
         }
@@ -323,7 +345,7 @@ pub(crate) fn local_run_msg(context: &mut ClientContext, params: ParamsOfLocalRu
     let (messages, fees) = if !tvm_call {
     #[cfg(feature = "fee_calculation")]
     {
-        let result = contract.local_call(msg)
+        let result = contract.local_call_with_clock(msg, context.clock())
             .map_err(|err| ApiError::contracts_local_run_failed(err))?;
         (result.messages, Some(LocalRunFees::from(result.fees)))
     }
@@ -332,7 +354,7 @@ pub(crate) fn local_run_msg(context: &mut ClientContext, params: ParamsOfLocalRu
         return Err(ApiError::contracts_local_run_failed("Fee calculation feature disabled"));
     }
     } else {
-        let messages = contract.local_call_tvm(msg)
+        let messages = contract.local_call_tvm_with_clock(msg, context.clock())
             .map_err(|err| ApiError::contracts_local_run_failed(err))?;
 
         (messages, None)
@@ -374,16 +396,18 @@ pub(crate) fn encode_message(context: &mut ClientContext, params: ParamsOfRun) -
         params.call_set.clone()
     );
 
+    context.sync_clock()?;
     let address = account_decode(&params.address)?;
     let key_pair = if let Some(keys) = params.key_pair { Some(keys.decode()?) } else { None };
 
-    let msg = Contract::construct_call_message_json(
+    let msg = Contract::construct_call_message_json_with_clock(
         address,
         params.call_set.into(),
         false,
         key_pair.as_ref(),
         Some(context.get_client()?.timeouts()),
-        params.try_index)
+        params.try_index,
+        context.clock())
         .map_err(|err| ApiError::contracts_create_run_message_failed(err))?;
 
     let (body, id) = serialize_message(msg.message)?;
@@ -397,11 +421,13 @@ pub(crate) fn encode_message(context: &mut ClientContext, params: ParamsOfRun) -
 }
 
 pub(crate) fn encode_unsigned_message(context: &mut ClientContext, params: ParamsOfEncodeUnsignedRunMessage) -> ApiResult<EncodedUnsignedMessage> {
-    let encoded = ton_sdk::Contract::get_call_message_bytes_for_signing(
+    context.sync_clock()?;
+    let encoded = ton_sdk::Contract::get_call_message_bytes_for_signing_with_clock(
         account_decode(&params.address)?,
         params.call_set.into(),
         Some(context.get_client()?.timeouts()),
-        params.try_index
+        params.try_index,
+        context.clock()
     ).map_err(|err| ApiError::contracts_create_run_message_failed(err))?;
     Ok(EncodedUnsignedMessage {
         unsigned_bytes_base64: base64::encode(&encoded.message),
@@ -410,6 +436,59 @@ pub(crate) fn encode_unsigned_message(context: &mut ClientContext, params: Param
     })
 }
 
+pub(crate) fn parse_value(value: &str) -> ApiResult<u128> {
+    let result = if value.starts_with("0x") {
+        u128::from_str_radix(&value[2..], 16)
+    } else {
+        u128::from_str_radix(value, 10)
+    };
+    result.map_err(|err| ApiError::invalid_params(value, err))
+}
+
+pub(crate) fn encode_internal_message(_context: &mut ClientContext, params: ParamsOfEncodeInternalMessage) -> ApiResult<EncodedMessage> {
+    debug!("-> contracts.run.internal.message({}, {:?})",
+        params.address.clone(),
+        params.call_set.clone()
+    );
+
+    let mut other_currencies = Vec::new();
+    for other in params.other_currencies.unwrap_or_default() {
+        other_currencies.push(OtherCurrencyValue { currency: other.currency, value: parse_value(&other.value)? });
+    }
+
+    let state_init = match params.image_base64 {
+        Some(image) => {
+            let bytes = base64::decode(&image)
+                .map_err(|err| ApiError::contracts_invalid_image(err))?;
+            let image = ContractImage::from_state_init(&mut bytes.as_slice())
+                .map_err(|err| ApiError::contracts_image_creation_failed(err))?;
+            Some(image.state_init())
+        },
+        None => None
+    };
+
+    let msg = Contract::construct_internal_message_json(
+        InternalMessageParams {
+            src: params.src_address.as_ref().map(|address| account_decode(address)).transpose()?,
+            dst: account_decode(&params.address)?,
+            value: parse_value(&params.value)?,
+            other_currencies,
+            bounce: params.bounce.unwrap_or(true),
+            state_init,
+        },
+        params.call_set.map(|call_set| call_set.into()))
+        .map_err(|err| ApiError::contracts_create_run_message_failed(err))?;
+
+    let (body, id) = serialize_message(msg)?;
+
+    debug!("<-");
+    Ok(EncodedMessage {
+        message_id: id,
+        message_body_base64: base64::encode(&body),
+        expire: None
+    })
+}
+
 pub(crate) fn decode_output(_context: &mut ClientContext, params: ParamsOfDecodeRunOutput) -> ApiResult<ResultOfRun> {
     let body = base64_decode(&params.body_base64)?;
     let result = Contract::decode_function_response_from_bytes_json(
@@ -0,0 +1,102 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use crate::client::ClientContext;
+use crate::contracts::EncodedMessage;
+use crate::contracts::run::{self, ParamsOfRun, RunFunctionCallSet, parse_value};
+use crate::crypto::keys::{KeyPair, account_decode};
+use crate::types::{ApiResult, ApiError, base64_decode};
+use ton_types::cells_serialization::BagOfCells;
+use ton_types::{Cell, deserialize_tree_of_cells};
+
+#[cfg(feature = "node_interaction")]
+use crate::contracts::run::ResultOfRun;
+
+// ABI of the multisig wallet. Transfers are sent with its `sendTransaction` function
+pub(crate) const WALLET_ABI: &str = include_str!("wallet.abi.json");
+pub(crate) const SEND_TRANSACTION_FUNCTION: &str = "sendTransaction";
+
+// Transfer fees are paid separately from the value, errors of the transfer are ignored
+const SEND_TRANSACTION_FLAGS: u8 = 3;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ParamsOfTransfer {
+    // Wallet address
+    pub address: String,
+    pub dest: String,
+    // Decimal or `0x` prefixed hex number of grams
+    pub value: String,
+    // `true` if not set
+    pub bounce: Option<bool>,
+    // Body of the transferred message (BOC)
+    pub payload_base64: Option<String>,
+    pub key_pair: KeyPair,
+    pub try_index: Option<u8>,
+}
+
+fn encode_payload(payload_base64: Option<&String>) -> ApiResult<String> {
+    let payload = match payload_base64 {
+        Some(payload) => {
+            let bytes = base64_decode(payload)?;
+            deserialize_tree_of_cells(&mut bytes.as_slice())
+                .map_err(|err| ApiError::contracts_create_send_grams_message_failed(err))?
+        },
+        None => Cell::default()
+    };
+
+    let mut data = Vec::new();
+    BagOfCells::with_root(&payload).write_to(&mut data, false)
+        .map_err(|err| ApiError::contracts_create_send_grams_message_failed(err))?;
+
+    Ok(base64::encode(&data))
+}
+
+fn run_params(params: ParamsOfTransfer) -> ApiResult<ParamsOfRun> {
+    let input = json!({
+        "dest": account_decode(&params.dest)?.to_string(),
+        "value": parse_value(&params.value)?.to_string(),
+        "bounce": params.bounce.unwrap_or(true),
+        "flags": SEND_TRANSACTION_FLAGS,
+        "payload": encode_payload(params.payload_base64.as_ref())?,
+    });
+
+    Ok(ParamsOfRun {
+        address: params.address,
+        call_set: RunFunctionCallSet {
+            abi: serde_json::from_str(WALLET_ABI)
+                .map_err(|err| ApiError::contracts_create_send_grams_message_failed(err))?,
+            function_name: SEND_TRANSACTION_FUNCTION.to_owned(),
+            header: None,
+            input,
+        },
+        key_pair: Some(params.key_pair),
+        try_index: params.try_index,
+    })
+}
+
+pub(crate) fn encode_message(context: &mut ClientContext, params: ParamsOfTransfer) -> ApiResult<EncodedMessage> {
+    debug!("-> contracts.transfer.message({}, {}, {})", params.address, params.dest, params.value);
+
+    let params = run_params(params)?;
+    run::encode_message(context, params)
+}
+
+#[cfg(feature = "node_interaction")]
+pub(crate) async fn transfer(context: &mut ClientContext, params: ParamsOfTransfer) -> ApiResult<ResultOfRun> {
+    debug!("-> contracts.transfer({}, {}, {})", params.address, params.dest, params.value);
+
+    let params = run_params(params)?;
+    run::run(context, params).await
+}
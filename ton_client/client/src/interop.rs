@@ -30,6 +30,14 @@ pub fn json_sync_request(
     method_name: String,
     params_json: String,
 ) -> JsonResponse {
+    // served without the client lock which is held by the request being cancelled
+    #[cfg(feature = "node_interaction")]
+    {
+        if method_name == "cancel" {
+            cancel_operations(context);
+            return JsonResponse::from_result("null".to_owned());
+        }
+    }
     Client::shared().json_sync_request(
         context,
         method_name,
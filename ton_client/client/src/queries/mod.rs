@@ -20,23 +20,35 @@ pub(crate) fn register(handlers: &mut DispatchTable) {
     handlers.spawn("queries.query", 
         |context: &mut crate::client::ClientContext, params: query::ParamsOfQuery| {
             let mut runtime = context.take_runtime()?;
-            let result = runtime.block_on(query::query(context, params));
+            let result = runtime.block_on(crate::client::cancellable(context.handle, query::query(context, params)));
+            context.runtime = Some(runtime);
+            result
+        });
+    handlers.spawn("queries.query.paginated",
+        |context: &mut crate::client::ClientContext, params: query::ParamsOfPaginatedQuery| {
+            let mut runtime = context.take_runtime()?;
+            let result = runtime.block_on(crate::client::cancellable(context.handle, query::query_paginated(context, params)));
             context.runtime = Some(runtime);
             result
         });
     handlers.spawn("queries.wait.for",
         |context: &mut crate::client::ClientContext, params: query::ParamsOfWaitFor| {
             let mut runtime = context.take_runtime()?;
-            let result = runtime.block_on(query::wait_for(context, params));
+            let result = runtime.block_on(crate::client::cancellable(context.handle, query::wait_for(context, params)));
             context.runtime = Some(runtime);
             result
         });
     handlers.spawn("queries.subscribe",
-        query::subscribe);
+        |context: &mut crate::client::ClientContext, params: query::ParamsOfSubscribe| {
+            let mut runtime = context.take_runtime()?;
+            let result = runtime.block_on(crate::client::cancellable(context.handle, query::subscribe(context, params)));
+            context.runtime = Some(runtime);
+            result
+        });
     handlers.spawn("queries.get.next",
         |context: &mut crate::client::ClientContext, params: query::SubscribeHandle| {
             let mut runtime = context.take_runtime()?;
-            let result = runtime.block_on(query::get_next(context, params));
+            let result = runtime.block_on(crate::client::cancellable(context.handle, query::get_next(context, params)));
             context.runtime = Some(runtime);
             result
         });
@@ -26,7 +26,24 @@ pub(crate) struct ParamsOfQuery {
     pub filter: String,
     pub result: String,
     pub order: Option<ton_sdk::OrderBy>,
-    pub limit: Option<u32>
+    pub limit: Option<u32>,
+    // Time in ms the query must be completed within, including network retries
+    pub deadline: Option<u32>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ParamsOfPaginatedQuery {
+    pub table: String,
+    pub filter: String,
+    pub result: String,
+    // Field records are ordered by, must be unique among records. `id` by default
+    pub key: Option<String>,
+    pub page_size: Option<u32>,
+    // Maximum number of records to return, all records are returned by default
+    pub limit: Option<u32>,
+    // Time in ms all pages must be loaded within
+    pub deadline: Option<u32>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -41,7 +58,10 @@ pub(crate) struct ParamsOfWaitFor {
     pub table: String,
     pub filter: String,
     pub result: String,
-    pub timeout: Option<u32>
+    // Server side time in ms to wait for the record
+    pub timeout: Option<u32>,
+    // Client side time in ms the whole operation must be completed within
+    pub deadline: Option<u32>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -64,25 +84,67 @@ lazy_static! {
 
 pub(crate) async fn query(context: &mut ClientContext, params: ParamsOfQuery) -> ApiResult<ResultOfQuery> {
     let client = context.get_client()?;
-    let result = client.query(&params.table, &params.filter, &params.result, params.order, params.limit, Some(0))
+    let result = ton_sdk::with_deadline(
+            client.query(&params.table, &params.filter, &params.result, params.order, params.limit, Some(0)),
+            params.deadline)
         .await
+        .and_then(|result| result)
         .map_err(|err| crate::types::apierror_from_sdkerror(err, ApiError::queries_query_failed))?;
 
     Ok(ResultOfQuery{ result })
 }
 
+pub(crate) async fn query_paginated(context: &mut ClientContext, params: ParamsOfPaginatedQuery) -> ApiResult<ResultOfQuery> {
+    let deadline = params.deadline;
+    ton_sdk::with_deadline(load_pages(context, params), deadline)
+        .await
+        .unwrap_or_else(|err| Err(crate::types::apierror_from_sdkerror(err, ApiError::queries_query_failed)))
+}
+
+async fn load_pages(context: &mut ClientContext, params: ParamsOfPaginatedQuery) -> ApiResult<ResultOfQuery> {
+    let page_size = params.page_size.unwrap_or(ton_sdk::node_client::DEFAULT_PAGE_SIZE);
+    if page_size == 0 {
+        return Err(ApiError::invalid_params(
+            &format!("pageSize: {}", page_size), "page size must be greater than zero"));
+    }
+
+    let client = context.get_client()?;
+    let stream = client.query_all(
+        &params.table,
+        &params.filter,
+        &params.result,
+        params.key.as_ref().map(|key| key.as_str()).unwrap_or("id"),
+        page_size)
+        .map_err(|err| crate::types::apierror_from_sdkerror(err, ApiError::queries_query_failed))?;
+
+    let stream = stream.take(params.limit.map(|limit| limit as usize).unwrap_or(usize::max_value()));
+    futures::pin_mut!(stream);
+
+    let mut records = Vec::new();
+    while let Some(record) = stream.next().await {
+        records.push(record
+            .map_err(|err| crate::types::apierror_from_sdkerror(err, ApiError::queries_query_failed))?);
+    }
+
+    Ok(ResultOfQuery{ result: serde_json::Value::Array(records) })
+}
+
 pub(crate) async fn wait_for(context: &mut ClientContext, params: ParamsOfWaitFor) -> ApiResult<ResultOfQuery> {
     let client = context.get_client()?;
-    let result = client.wait_for(&params.table, &params.filter, &params.result, params.timeout)
+    let result = ton_sdk::with_deadline(
+            client.wait_for(&params.table, &params.filter, &params.result, params.timeout),
+            params.deadline)
         .await
+        .and_then(|result| result)
         .map_err(|err| crate::types::apierror_from_sdkerror(err, ApiError::queries_wait_for_failed))?;
 
     Ok(ResultOfQuery{ result })
 }
 
-pub(crate) fn subscribe(context: &mut ClientContext, params: ParamsOfSubscribe) -> ApiResult<SubscribeHandle> {
+pub(crate) async fn subscribe(context: &mut ClientContext, params: ParamsOfSubscribe) -> ApiResult<SubscribeHandle> {
     let client = context.get_client()?;
     let stream = client.subscribe(&params.table, &params.filter, &params.result)
+        .await
         .map_err(|err| ApiError::queries_subscribe_failed(err))?;
 
     let mut rng = rand::rngs::OsRng::new()
@@ -94,18 +156,45 @@ pub(crate) fn subscribe(context: &mut ClientContext, params: ParamsOfSubscribe)
     Ok(SubscribeHandle{ handle })
 }
 
+// Returns the stream back to the handles when dropped, so the subscription survives
+// cancellation of the `get_next` request
+struct StreamGuard {
+    handle: StreamHandle,
+    stream: Option<Box<dyn Stream<Item=Result<serde_json::Value, failure::Error>> + Send + Unpin>>,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            add_handle(self.handle, stream);
+        }
+    }
+}
+
 pub(crate) async fn get_next(_context: &mut ClientContext, params: SubscribeHandle) -> ApiResult<ResultOfQuery> {
-    let mut stream = extract_handle(&params.handle)
+    let stream = extract_handle(&params.handle)
         .ok_or(ApiError::queries_get_next_failed("Invalid handle"))?;
-
-    let result = stream.by_ref()
-        .next()
-        .await
-        .ok_or(ApiError::queries_get_next_failed("None value"))?
+    let mut guard = StreamGuard { handle: params.handle, stream: Some(stream) };
+
+    let result = match guard.stream.as_mut() {
+        Some(stream) => stream.next().await,
+        None => None
+    };
+
+    // subscription stays alive after errors (e.g. reconnection notification),
+    // it is removed only when the stream is finished or unsubscribed
+    let result = match result {
+        Some(result) => result,
+        None => {
+            guard.stream = None;
+            return Err(ApiError::queries_get_next_failed("None value"));
+        }
+    };
+    drop(guard);
+
+    let result = result
         .map_err(|err| crate::types::apierror_from_sdkerror(err, ApiError::queries_get_next_failed))?;
 
-    add_handle(params.handle, stream);
-
     Ok(ResultOfQuery{ result: result })
 }
 
@@ -18,6 +18,8 @@ use crate::types::ApiResult;
 use crate::types::ApiError;
 
 use ton_sdk::{NodeClientConfig, TimeoutsConfig};
+#[cfg(feature = "node_interaction")]
+use ton_sdk::RetryConfig;
 
 pub(crate) fn register(handlers: &mut DispatchTable) {
     #[cfg(feature = "node_interaction")]
@@ -27,6 +29,39 @@ pub(crate) fn register(handlers: &mut DispatchTable) {
 
     handlers.call("setup", setup);
     handlers.call_no_args("version", |_|Ok(env!("CARGO_PKG_VERSION")));
+
+    #[cfg(feature = "node_interaction")]
+    handlers.call_no_args("client.server.info",
+        |context: &mut ClientContext| {
+            let mut runtime = context.take_runtime()?;
+            let result = runtime.block_on(crate::client::cancellable(context.handle, server_info(context)));
+            context.runtime = Some(runtime);
+            result
+        });
+}
+
+#[cfg(feature = "node_interaction")]
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub(crate) struct ResultOfServerInfo {
+    pub version: Option<String>,
+    pub features: Vec<String>,
+    pub global_id: Option<i32>,
+    pub zerostate_hash: Option<String>,
+}
+
+#[cfg(feature = "node_interaction")]
+async fn server_info(context: &mut ClientContext) -> ApiResult<ResultOfServerInfo> {
+    let info = context.get_client()?.server_info()
+        .await
+        .map_err(|err| crate::types::apierror_from_sdkerror(err, ApiError::server_info_failed))?;
+
+    Ok(ResultOfServerInfo {
+        version: info.version,
+        features: info.features,
+        global_id: info.global_id,
+        zerostate_hash: info.zerostate_hash,
+    })
 }
 
 
@@ -34,6 +69,7 @@ pub(crate) fn register(handlers: &mut DispatchTable) {
 #[serde(rename_all="camelCase")]
 pub(crate) struct SetupParams {
     pub base_url: Option<String>,
+    pub endpoints: Option<Vec<String>>,
     pub message_retries_count: Option<u8>,
     pub message_expiration_timeout: Option<u32>,
     pub message_expiration_timeout_grow_factor: Option<f32>,
@@ -41,6 +77,15 @@ pub(crate) struct SetupParams {
     pub message_processing_timeout_grow_factor: Option<f32>,
     pub wait_for_timeout: Option<u32>,
     pub access_key: Option<String>,
+    pub network_attempts: Option<u32>,
+    pub network_retry_delay: Option<u32>,
+    pub network_retry_delay_grow_factor: Option<f32>,
+    pub network_retry_max_delay: Option<u32>,
+    pub network_retryable_statuses: Option<Vec<u16>>,
+    pub expected_zerostate_hash: Option<String>,
+    pub max_clock_offset: Option<u32>,
+    pub records_cache_size: Option<usize>,
+    pub wait_by_subscription: Option<bool>,
 }
 
 impl Into<NodeClientConfig> for SetupParams {
@@ -58,7 +103,28 @@ impl Into<NodeClientConfig> for SetupParams {
             #[cfg(feature = "node_interaction")]
             base_url: self.base_url,
             #[cfg(feature = "node_interaction")]
+            endpoints: self.endpoints,
+            #[cfg(feature = "node_interaction")]
             access_key: self.access_key,
+            #[cfg(feature = "node_interaction")]
+            expected_zerostate_hash: self.expected_zerostate_hash,
+            #[cfg(feature = "node_interaction")]
+            max_clock_offset: self.max_clock_offset,
+            #[cfg(feature = "node_interaction")]
+            records_cache_size: self.records_cache_size,
+            #[cfg(feature = "node_interaction")]
+            wait_by_subscription: self.wait_by_subscription,
+            #[cfg(feature = "node_interaction")]
+            retry: {
+                let default = RetryConfig::default();
+                Some(RetryConfig {
+                    max_attempts: self.network_attempts.unwrap_or(default.max_attempts),
+                    delay: self.network_retry_delay.unwrap_or(default.delay),
+                    delay_grow_factor: self.network_retry_delay_grow_factor.unwrap_or(default.delay_grow_factor),
+                    max_delay: self.network_retry_max_delay.unwrap_or(default.max_delay),
+                    retryable_statuses: self.network_retryable_statuses.unwrap_or(default.retryable_statuses),
+                })
+            },
         }
     }
 }
@@ -20,6 +20,8 @@ use serde_json::{Value, Map};
 use log::{Metadata, Record, LevelFilter};
 use crate::{tc_create_context, tc_destroy_context};
 use ton_block::MsgAddressInt;
+#[cfg(feature = "local_server")]
+use ton_sdk::local_server::LocalServer;
 use std::str::FromStr;
 
 pub const LOG_CGF_PATH: &str = "src/tests/log_cfg.yaml";
@@ -290,13 +292,15 @@ fn test_tg_mnemonic() {
 }
 
 #[test]
+#[cfg(feature = "local_server")]
 fn test_wallet_deploy() {
     let client = TestClient::new();
     let version = client.request("version", Value::Null).unwrap();
     println!("result: {}", version.to_string());
 
+    let server = LocalServer::start().unwrap();
     let _deployed = client.request("setup",
-        json!({"baseUrl": "http://localhost"})).unwrap();
+        json!({"baseUrl": server.url()})).unwrap();
 
     let keys = client.request("crypto.ed25519.keypair", json!({})).unwrap();
 
@@ -367,7 +371,9 @@ fn test_wallet_deploy() {
     assert_eq!("{\"output\":{\"value0\":\"0x0\"}}", result);
 }
 
+#[cfg(feature = "local_server")]
 const GIVER_ADDRESS: &str = "0:841288ed3b55d9cdafa806807f02a0ae0c169aa5edfe88a789a6482429756a94";
+#[cfg(feature = "local_server")]
 const GIVER_ABI: &str = r#"
 {
 	"ABI version": 1,
@@ -538,3 +544,373 @@ fn test_address_parsing() {
         })).unwrap(),
         base64_url);
 }
+
+#[test]
+#[cfg(feature = "local_server")]
+fn test_cancel_and_deadline() {
+    let server = LocalServer::with_transport(ton_sdk::memory_transport::InMemoryTransport::new()).unwrap();
+    let client = TestClient::new();
+    client.request("setup", json!({"baseUrl": server.url()})).unwrap();
+
+    let wait_params = |deadline: Option<u32>| json!({
+        "table": "messages",
+        "filter": json!({ "id": { "eq": "1" } }).to_string(),
+        "result": "id",
+        "timeout": 10000,
+        "deadline": deadline,
+    });
+    let error_code = |error: String| serde_json::from_str::<Value>(&error).unwrap()["code"].clone();
+
+    let started = std::time::Instant::now();
+    let error = client.request("queries.wait.for", wait_params(Some(200))).unwrap_err();
+    assert_eq!(error_code(error), json!(1008));
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+    // `cancel` is served while the context is busy with the wait
+    let context = client.context;
+    let cancel = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        crate::json_sync_request(context, "cancel".to_owned(), String::new())
+    });
+    let started = std::time::Instant::now();
+    let error = client.request("queries.wait.for", wait_params(None)).unwrap_err();
+    assert_eq!(error_code(error), json!(1007));
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert!(cancel.join().unwrap().error_json.is_empty());
+
+    // later requests are not affected by the cancellation
+    server.transport().add_record("messages", json!({ "id": "1" }));
+    let result = client.request("queries.wait.for", wait_params(Some(5000))).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&result).unwrap(), json!({ "result": { "id": "1" } }));
+}
+
+#[test]
+#[cfg(feature = "local_server")]
+fn test_query_paginated() {
+    let server = LocalServer::with_transport(ton_sdk::memory_transport::InMemoryTransport::new()).unwrap();
+    for i in 0..5 {
+        server.transport().add_record("messages", json!({ "id": format!("{}", i) }));
+    }
+    let client = TestClient::new();
+    client.request("setup", json!({"baseUrl": server.url()})).unwrap();
+
+    let result = client.request("queries.query.paginated", json!({
+        "table": "messages",
+        "filter": "{}",
+        "result": "id",
+        "pageSize": 2,
+        "limit": 3,
+    })).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Value>(&result).unwrap(),
+        json!({ "result": [{ "id": "0" }, { "id": "1" }, { "id": "2" }] }));
+
+    let error = client.request("queries.query.paginated", json!({
+        "table": "messages",
+        "filter": "{}",
+        "result": "id",
+        "pageSize": 0,
+    })).unwrap_err();
+    assert_eq!(serde_json::from_str::<Value>(&error).unwrap()["code"], json!(2));
+}
+
+#[test]
+#[cfg(feature = "local_server")]
+fn test_server_info() {
+    let server = LocalServer::with_transport(ton_sdk::memory_transport::InMemoryTransport::new()).unwrap();
+    server.transport().add_record("blocks", json!({
+        "id": "1",
+        "workchain_id": -1,
+        "seq_no": 1,
+        "global_id": 42,
+        "prev_ref": { "root_hash": "abcd" }
+    }));
+    let client = TestClient::new();
+    client.request("setup", json!({"baseUrl": server.url()})).unwrap();
+
+    let info: Value = serde_json::from_str(&client.request("client.server.info", Value::Null).unwrap()).unwrap();
+    assert_eq!(info["version"], json!(ton_sdk::memory_transport::SERVER_VERSION));
+    assert_eq!(info["globalId"], json!(42));
+    assert_eq!(info["zerostateHash"], json!("abcd"));
+    assert!(info["features"].as_array().unwrap().contains(&json!("messages")));
+
+    // message is not sent to the other network
+    let other = TestClient::new();
+    other.request("setup", json!({"baseUrl": server.url(), "expectedZerostateHash": "ef01"})).unwrap();
+    let error = other.request("contracts.send.message", json!({
+        "messageId": "00",
+        "messageBodyBase64": base64::encode("message"),
+    })).unwrap_err();
+    assert_eq!(serde_json::from_str::<Value>(&error).unwrap()["code"], json!(1009));
+    assert!(server.transport().sent_messages().is_empty());
+}
+
+#[test]
+fn test_internal_message() {
+    let client = TestClient::new();
+    let dst = "0:841288ed3b55d9cdafa806807f02a0ae0c169aa5edfe88a789a6482429756a94";
+
+    let encoded = client.request("contracts.run.internal.message", json!({
+        "address": dst,
+        "value": "0x3b9aca00",
+        "otherCurrencies": [{ "currency": 1, "value": "100" }],
+        "imageBase64": WALLET_CODE_BASE64,
+        "callSet": {
+            "abi": serde_json::from_str::<Value>(WALLET_ABI).unwrap(),
+            "functionName": "setSubscriptionAccount",
+            "input": { "addr": dst }
+        }
+    })).unwrap();
+    let encoded: Value = serde_json::from_str(&encoded).unwrap();
+    assert!(encoded["expire"].is_null());
+
+    let bytes = base64::decode(encoded["messageBodyBase64"].as_str().unwrap()).unwrap();
+    let msg = ton_sdk::Contract::deserialize_message(&bytes).unwrap();
+    match msg.header() {
+        ton_block::CommonMsgInfo::IntMsgInfo(header) => {
+            assert_eq!(header.src, ton_block::MsgAddressIntOrNone::None);
+            assert_eq!(header.dst, MsgAddressInt::from_str(dst).unwrap());
+            assert_eq!(header.value.grams, 1_000_000_000u128.into());
+            assert!(header.bounce);
+        },
+        _ => panic!("Internal message expected")
+    }
+    assert!(msg.state_init().is_some() && msg.body().is_some());
+
+    let error = client.request("contracts.run.internal.message", json!({
+        "address": dst,
+        "value": "1 ton",
+    })).unwrap_err();
+    assert!(error.contains("Invalid params"));
+}
+
+#[test]
+fn test_transfer_message() {
+    // message is encoded without the server, only expiration timeouts of the config are used
+    let client = TestClient::new();
+    client.request("setup", json!({})).unwrap();
+
+    let keys: Value = serde_json::from_str(&client.request("crypto.ed25519.keypair", json!({})).unwrap()).unwrap();
+    let wallet = "0:841288ed3b55d9cdafa806807f02a0ae0c169aa5edfe88a789a6482429756a94";
+    let dest = "0:fcb91a3a3816d0f7b8c2c76108b8a9bc5a6b7a55bd79f8ab101c52db29232260";
+
+    let encoded = client.request("contracts.transfer.message", json!({
+        "address": wallet,
+        "dest": dest,
+        "value": "1000000000",
+        "keyPair": keys,
+    })).unwrap();
+    let encoded: Value = serde_json::from_str(&encoded).unwrap();
+    assert!(encoded["expire"].is_number());
+
+    let bytes = base64::decode(encoded["messageBodyBase64"].as_str().unwrap()).unwrap();
+    let msg = ton_sdk::Contract::deserialize_message(&bytes).unwrap();
+    match msg.header() {
+        ton_block::CommonMsgInfo::ExtInMsgInfo(header) => assert_eq!(header.dst, MsgAddressInt::from_str(wallet).unwrap()),
+        _ => panic!("External inbound message expected")
+    }
+
+    let call = ton_sdk::Contract::decode_unknown_function_call_json(
+        crate::contracts::transfer::WALLET_ABI.to_owned(), msg.body().unwrap(), false).unwrap();
+    assert_eq!(call.function_name, crate::contracts::transfer::SEND_TRANSACTION_FUNCTION);
+    let input: Value = serde_json::from_str(&call.params).unwrap();
+    assert_eq!(input["dest"], json!(dest));
+    assert_eq!(input["bounce"], json!(true));
+
+    // payload must be a bag of cells
+    let error = client.request("contracts.transfer.message", json!({
+        "address": wallet,
+        "dest": dest,
+        "value": "1000000000",
+        "payloadBase64": base64::encode("not a boc"),
+        "keyPair": keys,
+    })).unwrap_err();
+    assert_eq!(serde_json::from_str::<Value>(&error).unwrap()["code"], json!(3013));
+}
+
+#[test]
+#[cfg(feature = "local_server")]
+fn test_encode_with_skewed_clock() {
+    let server = LocalServer::with_transport(ton_sdk::memory_transport::InMemoryTransport::new()).unwrap();
+    let client = TestClient::new();
+    client.request("setup", json!({"baseUrl": server.url()})).unwrap();
+
+    // local clock is an hour behind the server one
+    let now = chrono::Utc::now().timestamp();
+    {
+        let mut shared = crate::client::Client::shared();
+        let context = shared.required_context(client.context).unwrap();
+        let node_client = context.client.take().unwrap()
+            .with_clock(std::sync::Arc::new(ton_sdk::ManualClock::new((now - 3600) * 1000)));
+        context.client = Some(node_client);
+    }
+
+    // encoded message expires in server time
+    let keys: Value = serde_json::from_str(&client.request("crypto.ed25519.keypair", json!({})).unwrap()).unwrap();
+    let encoded = client.request("contracts.transfer.message", json!({
+        "address": GIVER_ADDRESS,
+        "dest": "0:fcb91a3a3816d0f7b8c2c76108b8a9bc5a6b7a55bd79f8ab101c52db29232260",
+        "value": "1000000000",
+        "keyPair": keys,
+    })).unwrap();
+    let expire = serde_json::from_str::<Value>(&encoded).unwrap()["expire"].as_i64().unwrap();
+    assert!(expire > now && expire < now + 600, "expire {}, now {}", expire, now);
+}
+
+#[test]
+#[cfg(feature = "local_server")]
+fn test_transfer() {
+    let server = LocalServer::start().unwrap();
+    let client = TestClient::new();
+    client.request("setup", json!({"baseUrl": server.url()})).unwrap();
+
+    // the local giver accepts wallet transfers
+    let keys: Value = serde_json::from_str(&client.request("crypto.ed25519.keypair", json!({})).unwrap()).unwrap();
+    let dest = "0:fcb91a3a3816d0f7b8c2c76108b8a9bc5a6b7a55bd79f8ab101c52db29232260";
+    let result = client.request("contracts.transfer", json!({
+        "address": GIVER_ADDRESS,
+        "dest": dest,
+        "value": "1000000000",
+        "bounce": false,
+        "keyPair": keys,
+    })).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&result).unwrap(), json!({ "output": null }));
+
+    let accounts = client.request("queries.query", json!({
+        "table": "accounts",
+        "filter": json!({ "id": { "eq": dest } }).to_string(),
+        "result": "balance",
+    })).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&accounts).unwrap(), json!({ "result": [{ "balance": "0x3b9aca00" }] }));
+}
+
+#[test]
+#[cfg(feature = "local_server")]
+fn test_process_message_steps() {
+    let server = LocalServer::start().unwrap();
+    let client = TestClient::new();
+    client.request("setup", json!({"baseUrl": server.url()})).unwrap();
+
+    let message = client.request("contracts.run.message", json!({
+        "address": GIVER_ADDRESS,
+        "abi": serde_json::from_str::<Value>(GIVER_ABI).unwrap(),
+        "functionName": "sendGrams",
+        "input": {
+            "dest": "0:fcb91a3a3816d0f7b8c2c76108b8a9bc5a6b7a55bd79f8ab101c52db29232260",
+            "amount": 1_000_000_000u64
+        },
+    })).unwrap();
+    let message: Value = serde_json::from_str(&message).unwrap();
+
+    let handle = client.request("contracts.process.message.start", json!({ "message": message })).unwrap();
+    let handle: Value = serde_json::from_str(&handle).unwrap();
+
+    let mut events = Vec::new();
+    loop {
+        let next: Value = serde_json::from_str(&client.request("contracts.process.message.next", handle.clone()).unwrap()).unwrap();
+        assert_eq!(next["event"]["messageId"], message["messageId"]);
+        events.push(next["event"]["type"].as_str().unwrap().to_owned());
+        if events.len() == 3 {
+            break;
+        }
+    }
+    assert_eq!(events, vec!["messageSent", "shardBlockFound", "transactionFound"]);
+
+    // the handle is released when processing is finished
+    assert!(client.request("contracts.process.message.next", handle.clone()).is_err());
+    assert!(client.request("contracts.process.message.stop", handle).is_err());
+}
+
+#[test]
+#[cfg(feature = "local_server")]
+fn test_process_message_resume() {
+    let server = LocalServer::start().unwrap();
+    let client = TestClient::new();
+    client.request("setup", json!({"baseUrl": server.url()})).unwrap();
+
+    let message = client.request("contracts.run.message", json!({
+        "address": GIVER_ADDRESS,
+        "abi": serde_json::from_str::<Value>(GIVER_ABI).unwrap(),
+        "functionName": "sendGrams",
+        "input": {
+            "dest": "0:fcb91a3a3816d0f7b8c2c76108b8a9bc5a6b7a55bd79f8ab101c52db29232260",
+            "amount": 1_000_000_000u64
+        },
+    })).unwrap();
+    let message: Value = serde_json::from_str(&message).unwrap();
+
+    let handle = client.request("contracts.process.message.start", json!({ "message": message })).unwrap();
+    let handle: Value = serde_json::from_str(&handle).unwrap();
+
+    let state: Value = serde_json::from_str(&client.request("contracts.process.message.state", handle.clone()).unwrap()).unwrap();
+    assert_eq!(state["sent"], false);
+
+    client.request("contracts.process.message.next", handle.clone()).unwrap();
+    let state: Value = serde_json::from_str(&client.request("contracts.process.message.state", handle.clone()).unwrap()).unwrap();
+    assert_eq!(state["messageId"], message["messageId"]);
+    assert_eq!(state["messageBodyBase64"], message["messageBodyBase64"]);
+    assert_eq!(state["sent"], true);
+    assert!(state["startTime"].is_number());
+
+    // processing is lost, e.g. the application is restarted
+    client.request("contracts.process.message.stop", handle).unwrap();
+
+    client.request("contracts.process.message.resume", json!({
+        "abi": serde_json::from_str::<Value>(GIVER_ABI).unwrap(),
+        "functionName": "sendGrams",
+        "state": state,
+    })).unwrap();
+}
+
+#[test]
+#[cfg(feature = "local_server")]
+fn test_run_start_retries_expired_message() {
+    let server = LocalServer::with_transport(ton_sdk::memory_transport::InMemoryTransport::new()).unwrap();
+    // master block proving that any sent message is expired
+    server.transport().add_record("blocks", json!({
+        "id": "1",
+        "workchain_id": -1,
+        "master": { "min_shard_gen_utime": u32::max_value() },
+        "in_msg_descr": [{ "transaction_id": "1" }]
+    }));
+    server.transport().add_record("transactions", json!({ "id": "1" }));
+    let client = TestClient::new();
+    client.request("setup", json!({"baseUrl": server.url(), "messageRetriesCount": 1})).unwrap();
+
+    let keys: Value = serde_json::from_str(&client.request("crypto.ed25519.keypair", json!({})).unwrap()).unwrap();
+    let handle = client.request("contracts.run.start", json!({
+        "address": "0:841288ed3b55d9cdafa806807f02a0ae0c169aa5edfe88a789a6482429756a94",
+        "abi": serde_json::from_str::<Value>(crate::contracts::transfer::WALLET_ABI).unwrap(),
+        "functionName": crate::contracts::transfer::SEND_TRANSACTION_FUNCTION,
+        "input": {
+            "dest": "0:fcb91a3a3816d0f7b8c2c76108b8a9bc5a6b7a55bd79f8ab101c52db29232260",
+            "value": "1000000000",
+            "bounce": false,
+            "flags": 3,
+            "payload": "te6ccgEBAQEAAgAAAA==",
+        },
+        "keyPair": keys,
+    })).unwrap();
+    let handle: Value = serde_json::from_str(&handle).unwrap();
+
+    // handles are not shared between contexts
+    let other = TestClient::new();
+    other.request("setup", json!({"baseUrl": server.url()})).unwrap();
+    assert!(other.request("contracts.process.message.state", handle.clone()).is_err());
+
+    let mut events = Vec::new();
+    for _ in 0..5 {
+        let next: Value = serde_json::from_str(&client.request("contracts.process.message.next", handle.clone()).unwrap()).unwrap();
+        events.push((next["event"]["type"].as_str().unwrap().to_owned(), next["event"]["tryIndex"].clone()));
+    }
+    assert_eq!(events, vec![
+        ("messageSent".to_owned(), json!(0)),
+        ("expired".to_owned(), json!(0)),
+        ("retrying".to_owned(), json!(1)),
+        ("messageSent".to_owned(), json!(1)),
+        ("expired".to_owned(), json!(1)),
+    ]);
+
+    // retries are exhausted
+    assert!(client.request("contracts.process.message.next", handle).is_err());
+}
@@ -16,7 +16,7 @@ use serde_json::Value;
 
 use crate::client::ClientContext;
 use crate::types::{ApiResult, ApiError};
-use ton_sdk::Contract;
+use ton_sdk::{Clock, Contract};
 use crate::dispatch::DispatchTable;
 
 #[derive(Serialize, Deserialize)]
@@ -42,13 +42,13 @@ const DEFAULT_BALANCE: &str = "0xffffffffffffffff";
 const DEFAULT_LAST_PAID: u32 = 1;
 
 pub(crate) fn get(
-    _context: &mut ClientContext,
+    context: &mut ClientContext,
     params: ParamsOfLocalRunGet,
 ) -> ApiResult<ResultOfLocalRunGet> {
     debug!("-> contracts.run.get({})",
         params.functionName,
     );
-    let last_paid = params.last_paid.unwrap_or(Contract::now().unwrap_or(DEFAULT_LAST_PAID));
+    let last_paid = params.last_paid.unwrap_or(context.clock().now().unwrap_or(DEFAULT_LAST_PAID));
     let contract_json = json!({
         "id": params.address.unwrap_or(DEFAULT_ADDRESS.to_string()),
         "acc_type": 1,
@@ -59,9 +59,10 @@ pub(crate) fn get(
     });
     let contract = Contract::from_json(contract_json.to_string().as_str())
         .map_err(|err| ApiError::contracts_local_run_failed(err))?;
-    let output = contract.local_call_tvm_get_json(
+    let output = contract.local_call_tvm_get_json_with_clock(
         &params.functionName,
-        params.input.as_ref()
+        params.input.as_ref(),
+        context.clock()
     ).map_err(|err| ApiError::contracts_local_run_failed(err))?;
     Ok(ResultOfLocalRunGet { output })
 }
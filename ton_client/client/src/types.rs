@@ -53,9 +53,25 @@ impl ApiErrorSource {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct ApiErrorData {
-    pub transaction_id: String,
-    pub phase: String,
+pub struct ApiGraphqlError {
+    pub message: String,
+    // `extensions.code` of the error if provided by the server
+    pub code: Option<String>,
+    pub locations: Option<serde_json::Value>,
+    pub path: Option<serde_json::Value>,
+    pub extensions: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum ApiErrorData {
+    Transaction {
+        transaction_id: String,
+        phase: String,
+    },
+    Graphql {
+        graphql_errors: Vec<ApiGraphqlError>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -152,6 +168,31 @@ impl ApiError {
             "Message expired")
     }
 
+    pub fn operation_cancelled() -> Self {
+        sdk_err!(OperationCancelled,
+            "Operation was cancelled")
+    }
+
+    pub fn operation_timeout() -> Self {
+        sdk_err!(OperationTimeout,
+            "Operation was not completed before the deadline")
+    }
+
+    pub fn wrong_network<E: Display>(err: E) -> Self {
+        sdk_err!(WrongNetwork,
+            "{}", err)
+    }
+
+    pub fn clock_out_of_sync<E: Display>(err: E) -> Self {
+        sdk_err!(ClockOutOfSync,
+            "{}", err)
+    }
+
+    pub fn server_info_failed<E: Display>(err: E) -> Self {
+        sdk_err!(ServerInfoFailed,
+            "Server info request failed: {}", err)
+    }
+
     // SDK Cell
 
     pub fn cell_invalid_query<E: Display>(s: E) -> Self {
@@ -419,6 +460,11 @@ impl ApiError {
             "Get next failed: {}", err)
     }
 
+    pub fn queries_subscription_reconnected() -> Self {
+        sdk_err!(QueriesSubscriptionReconnected,
+            "Subscription connection was restored, some updates could be missed")
+    }
+
     // Failed transaction phases
 
     pub fn transaction_parse_failed() -> ApiError {
@@ -435,7 +481,7 @@ impl ApiError {
             &(-1i32),
             "Transaction aborted".to_string()
         );
-         error.data = Some(ApiErrorData{
+         error.data = Some(ApiErrorData::Transaction {
             transaction_id: tr_id,
             phase: "unknown".to_string(),
         });
@@ -444,7 +490,7 @@ impl ApiError {
 
     pub fn tvm_execution_skipped(tr_id: String, reason: &ComputeSkipReason) -> ApiError {
         let mut error = ApiError::new(ApiErrorSource::Node, reason, reason.as_string());
-        error.data = Some(ApiErrorData{
+        error.data = Some(ApiErrorData::Transaction {
             transaction_id: tr_id,
             phase: "computeSkipped".to_string(),
         });
@@ -458,7 +504,7 @@ impl ApiError {
             format!("VM terminated with exit code: {}", exit_code),
         );
 
-        error.data = Some(ApiErrorData{
+        error.data = Some(ApiErrorData::Transaction {
             transaction_id: tr_id,
             phase: "computeVm".to_string(),
         });
@@ -467,7 +513,7 @@ impl ApiError {
 
     pub fn storage_phase_failed(tr_id: String, reason: &AccStatusChange) -> ApiError {
         let mut error = ApiError::new(ApiErrorSource::Node, reason, reason.as_string());
-        error.data = Some(ApiErrorData{
+        error.data = Some(ApiErrorData::Transaction {
             transaction_id: tr_id,
             phase: "storage".to_string(),
         });
@@ -482,7 +528,7 @@ impl ApiError {
     ) -> ApiError {
         let code = ApiActionCode::new(result_code, valid, no_funds);
         let mut error = ApiError::new(ApiErrorSource::Node, &code, code.as_string());
-        error.data = Some(ApiErrorData{
+        error.data = Some(ApiErrorData::Transaction {
             transaction_id: tr_id,
             phase: "action".to_string(),
         });
@@ -501,6 +547,11 @@ pub enum ApiSdkErrorCode {
     ConfigInitFailed = 1001,
     WaitForTimeout = 1003,
     MessageExpired = 1006,
+    OperationCancelled = 1007,
+    OperationTimeout = 1008,
+    WrongNetwork = 1009,
+    ServerInfoFailed = 1010,
+    ClockOutOfSync = 1011,
 
     CryptoInvalidPublicKey = 2001,
     CryptoInvalidSecretKey = 2002,
@@ -555,6 +606,7 @@ pub enum ApiSdkErrorCode {
     QueriesSubscribeFailed = 4002,
     QueriesWaitForFailed = 4003,
     QueriesGetNextFailed = 4004,
+    QueriesSubscriptionReconnected = 4005,
 
     CellInvalidQuery = 5001,
 }
@@ -644,6 +696,29 @@ where
     match err.downcast_ref::<SdkError>() {
         Some(SdkError::WaitForTimeout) => ApiError::wait_for_timeout(),
         Some(SdkError::MessageExpired) => ApiError::message_expired(),
+        Some(SdkError::OperationCancelled) => ApiError::operation_cancelled(),
+        Some(SdkError::OperationTimeout) => ApiError::operation_timeout(),
+        Some(err @ SdkError::WrongNetwork { .. }) => ApiError::wrong_network(err),
+        Some(err @ SdkError::ClockOutOfSync { .. }) => ApiError::clock_out_of_sync(err),
+        Some(SdkError::SubscriptionReconnected) => ApiError::queries_subscription_reconnected(),
+        // errors returned by the server are passed to the caller as is, so they can be
+        // distinguished by their codes
+        #[cfg(feature = "node_interaction")]
+        Some(SdkError::Graphql { err: graphql_err }) if !graphql_err.errors().is_empty() => {
+            let graphql_errors = graphql_err.errors()
+                .iter()
+                .map(|error| ApiGraphqlError {
+                    message: error.message.clone(),
+                    code: error.code().map(|code| code.to_owned()),
+                    locations: error.locations.clone(),
+                    path: error.path.clone(),
+                    extensions: error.extensions.clone(),
+                })
+                .collect();
+            let mut error = default_err(err);
+            error.data = Some(ApiErrorData::Graphql { graphql_errors });
+            error
+        },
         _ => default_err(err)
     }
 }
@@ -0,0 +1,108 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use crate::error::SdkError;
+use futures::future::{AbortHandle, Abortable};
+use futures::Future;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use ton_types::Result;
+
+#[derive(Default)]
+struct TokenState {
+    cancelled: bool,
+    next_id: u64,
+    running: HashMap<u64, AbortHandle>,
+}
+
+// Client side cancellation of asynchronous operations. Clones share the same state,
+// so the token can be cancelled from another task or thread while operations run
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<Mutex<TokenState>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Aborts all operations running with the token. Operations started later fail at once
+    pub fn cancel(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.cancelled = true;
+        for (_, handle) in state.running.drain() {
+            handle.abort();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.lock().unwrap().cancelled
+    }
+
+    // Runs the future until it completes or the token is cancelled. Cancelled future
+    // is dropped and `SdkError::OperationCancelled` is returned
+    pub async fn run<F: Future>(&self, future: F) -> Result<F::Output> {
+        let (handle, registration) = AbortHandle::new_pair();
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            if state.cancelled {
+                return Err(SdkError::OperationCancelled.into());
+            }
+            state.next_id += 1;
+            let id = state.next_id;
+            state.running.insert(id, handle);
+            id
+        };
+
+        // the handle is removed even if the future is dropped before completion
+        let _guard = RunningGuard { state: &self.state, id };
+        Abortable::new(future, registration)
+            .await
+            .map_err(|_| SdkError::OperationCancelled.into())
+    }
+
+    #[cfg(test)]
+    fn running_count(&self) -> usize {
+        self.state.lock().unwrap().running.len()
+    }
+}
+
+struct RunningGuard<'a> {
+    state: &'a Mutex<TokenState>,
+    id: u64,
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().running.remove(&self.id);
+    }
+}
+
+// Runs the future failing with `SdkError::OperationTimeout` if it is not completed
+// within `deadline` ms. Unlike server side `timeout` of queries the deadline covers
+// the whole operation including network retries
+pub async fn with_deadline<F: Future>(future: F, deadline: Option<u32>) -> Result<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout(Duration::from_millis(deadline as u64), future)
+            .await
+            .map_err(|_| SdkError::OperationTimeout.into()),
+        None => Ok(future.await)
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/test_cancellation.rs"]
+mod tests;
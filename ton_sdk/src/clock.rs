@@ -0,0 +1,67 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use chrono::Utc;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use ton_types::Result;
+
+// Source of the current time used for message expiration and local contract runs.
+// `NodeClient` is a clock itself: it corrects its clock by the server clock offset
+pub trait Clock: Send + Sync {
+    // Unix time in ms
+    fn now_ms(&self) -> i64;
+
+    // Unix time in seconds
+    fn now(&self) -> Result<u32> {
+        Ok(u32::try_from(self.now_ms() / 1000)?)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> i64 {
+        Utc::now().timestamp_millis()
+    }
+}
+
+// Clock which time is changed only explicitly, e.g. to freeze or advance time in tests.
+// Clones share the same time
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    time: Arc<AtomicI64>,
+}
+
+impl ManualClock {
+    pub fn new(time_ms: i64) -> Self {
+        Self { time: Arc::new(AtomicI64::new(time_ms)) }
+    }
+
+    pub fn set(&self, time_ms: i64) {
+        self.time.store(time_ms, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, duration_ms: i64) {
+        self.time.fetch_add(duration_ms, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ms(&self) -> i64 {
+        self.time.load(Ordering::SeqCst)
+    }
+}
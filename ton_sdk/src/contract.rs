@@ -15,17 +15,16 @@
 use crate::json_helper;
 use crate::local_tvm;
 use crate::error::SdkError;
-use crate::{AbiContract, Message, MessageId, TimeoutsConfig};
+use crate::{AbiContract, Clock, Message, MessageId, SystemClock, TimeoutsConfig};
 
 use ed25519_dalek::{Keypair, PublicKey};
-use chrono::prelude::Utc;
-use std::convert::{Into, TryFrom};
+use std::convert::Into;
 use std::io::{Cursor, Read, Seek};
 use std::slice::Iter;
 use ton_block::{
     Account, AccountState, AccountStatus, AccountStorage, CurrencyCollection, Deserializable,
-    ExternalInboundMessageHeader, GetRepresentationHash, Message as TvmMessage, MsgAddressInt,
-    Serializable, StateInit, StorageInfo};
+    ExternalInboundMessageHeader, GetRepresentationHash, InternalMessageHeader, Message as TvmMessage,
+    MsgAddressInt, MsgAddressIntOrNone, Serializable, StateInit, StorageInfo};
 use ton_types::cells_serialization::{deserialize_cells_tree, BagOfCells};
 use ton_types::{error, fail, Result, AccountId, Cell, SliceData, HashmapE};
 use ton_abi::json_abi::DecodedMessage;
@@ -40,18 +39,14 @@ use ton_executor::BlockchainConfig;
 use crate::{
     NodeClient, Transaction,
     json_helper::account_status_to_u8,
-    transaction::TRANSACTION_FIELDS_ORDINARY,
-    types::{BLOCKS_TABLE_NAME, CONTRACTS_TABLE_NAME, TRANSACTIONS_TABLE_NAME},
+    processing::{self, MessageProcessing, ProcessingEvent, ProcessingObserver},
+    query_builder::{AccountField, Accounts, Fields, Filter},
 };
 use std::{
     collections::HashMap,
     iter::FromIterator,
 };
-#[cfg(feature = "node_interaction")]
-use ton_block::TransactionProcessingStatus;
 use serde_json::Value;
-#[cfg(feature = "node_interaction")]
-use futures::FutureExt;
 use ton_vm::stack::{StackItem, Stack};
 use ton_vm::stack::integer::IntegerData;
 use std::sync::Arc;
@@ -147,24 +142,24 @@ impl StackItemJSON {
 }
 
 #[cfg(feature = "node_interaction")]
-const ACCOUNT_FIELDS: &str = r#"
-    id
-    acc_type
-    balance
-    balance_other {
-        currency
-        value
-    }
-    code
-    data
-    last_paid
-"#;
+fn account_fields() -> Fields<Accounts> {
+    Fields::new(&[
+        AccountField::Id,
+        AccountField::AccType,
+        AccountField::Balance,
+        AccountField::BalanceOtherCurrency,
+        AccountField::BalanceOtherValue,
+        AccountField::Code,
+        AccountField::Data,
+        AccountField::LastPaid,
+    ])
+}
 
 // The struct represents value of some addititonal currency
 #[derive(Deserialize, Default, Debug, Clone)]
 pub struct OtherCurrencyValue {
-    currency: u32,
-    value: u128,
+    pub currency: u32,
+    pub value: u128,
 }
 
 // The struct represents smart contract and allows
@@ -195,6 +190,21 @@ pub struct FunctionCallSet {
     pub abi: String,
 }
 
+// Parameters of an internal message, i.e. a message sent by one contract to another
+#[derive(Clone, Debug, Default)]
+pub struct InternalMessageParams {
+    // `None` makes message without source address
+    pub src: Option<MsgAddressInt>,
+    pub dst: MsgAddressInt,
+    // Value in grams
+    pub value: u128,
+    pub other_currencies: Vec<OtherCurrencyValue>,
+    // Bounced message returns the value to the source if the destination fails to process it
+    pub bounce: bool,
+    // Deploys contract to the destination address if it is not deployed yet
+    pub state_init: Option<StateInit>,
+}
+
 pub struct SdkMessage {
     pub message: TvmMessage,
     pub expire: Option<u32>,
@@ -360,10 +370,7 @@ impl Contract {
     pub async fn load(client: &NodeClient, address: &MsgAddressInt) -> Result<Option<Contract>> {
         let id = address.to_string();
 
-        let value = client.load_record_fields(
-            CONTRACTS_TABLE_NAME,
-            &id,
-            ACCOUNT_FIELDS).await?;
+        let value = client.load_record_fields_typed(&id, &account_fields()).await?;
 
         if value == serde_json::Value::Null {
             Ok(None)
@@ -379,16 +386,11 @@ impl Contract {
     pub async fn load_wait_deployed(client: &NodeClient, address: &MsgAddressInt, timeout: Option<u32>)
         -> Result<Contract>
     {
-        let value = client.wait_for(
-            CONTRACTS_TABLE_NAME,
-            &json!({
-                "id": {
-                    "eq": address.to_string()
-                },
-                "acc_type": { "eq": account_status_to_u8(AccountStatus::AccStateActive) }
-            }).to_string(),
-            ACCOUNT_FIELDS,
-            timeout).await?;
+        let filter = Filter::<Accounts>::new()
+            .eq(AccountField::Id, address.to_string())
+            .eq(AccountField::AccType, account_status_to_u8(AccountStatus::AccStateActive));
+
+        let value = client.wait_for_typed(&filter, &account_fields(), timeout).await?;
 
         serde_json::from_value(value)
             .map_err(|err| SdkError::InvalidData {
@@ -399,21 +401,21 @@ impl Contract {
     // Asynchronously loads a Contract's json representation
     // or null if message with given id is not exists
     pub async fn load_json(client: &NodeClient, id: AccountId) -> Result<String> {
-        client.load_record_fields(CONTRACTS_TABLE_NAME, &id.to_hex_string(), ACCOUNT_FIELDS)
+        client.load_record_fields_typed(&id.to_hex_string(), &account_fields())
             .await
             .map(|val| val.to_string())
     }
 
-    const MESSAGE_EXPIRED_CODE: i32 = 57;
-    const REPLAY_PROTECTION_CODE: i32 = 52;
-
-    async fn retry_call<F, Fut>(retries_count: u8, func: F) -> Result<Transaction>
+    async fn retry_call<F, Fut>(retries_count: u8, events: Option<&dyn ProcessingObserver>, func: F) -> Result<Transaction>
         where
             F: Fn(u8) -> Result<Fut>,
             Fut: futures::Future<Output=Result<Transaction>>
     {
         for i in 0..(retries_count + 1) {
             //println!("Try#{}", i);
+            if i > 0 {
+                processing::notify(events, ProcessingEvent::Retrying { try_index: i });
+            }
             let result = func(i)?.await;
             match &result {
                 Err(error) => {
@@ -438,16 +440,29 @@ impl Contract {
         params: FunctionCallSet,
         key_pair: Option<&Keypair>,
     ) -> Result<Transaction> {
-        Self::retry_call(client.timeouts().message_retries_count, |try_index: u8| {
-            let msg = Self::construct_call_message_json(
+        Self::call_json_with_events(client, address, params, key_pair, None).await
+    }
+
+    // Same as `call_json` reporting processing events to `events`
+    pub async fn call_json_with_events(
+        client: &NodeClient,
+        address: MsgAddressInt,
+        params: FunctionCallSet,
+        key_pair: Option<&Keypair>,
+        events: Option<&dyn ProcessingObserver>,
+    ) -> Result<Transaction> {
+        client.sync_clock_once().await?;
+        Self::retry_call(client.timeouts().message_retries_count, events, |try_index: u8| {
+            let msg = Self::construct_call_message_json_with_clock(
                 address.clone(),
                 params.clone(),
                 false,
                 key_pair,
                 Some(client.timeouts()),
-                Some(try_index))?;
+                Some(try_index),
+                client)?;
 
-            Ok(Self::process_message(client, msg.message, msg.expire, try_index))
+            Ok(Self::process_message_with_events(client, msg.message, msg.expire, try_index, events))
         }).await
     }
 
@@ -462,16 +477,30 @@ impl Contract {
         key_pair: Option<&Keypair>,
         workchain_id: i32,
     ) -> Result<Transaction> {
-        Self::retry_call(client.timeouts().message_retries_count, |try_index: u8| {
-            let msg = Self::construct_deploy_message_json(
+        Self::deploy_json_with_events(client, params, image, key_pair, workchain_id, None).await
+    }
+
+    // Same as `deploy_json` reporting processing events to `events`
+    pub async fn deploy_json_with_events(
+        client: &NodeClient,
+        params: FunctionCallSet,
+        image: ContractImage,
+        key_pair: Option<&Keypair>,
+        workchain_id: i32,
+        events: Option<&dyn ProcessingObserver>,
+    ) -> Result<Transaction> {
+        client.sync_clock_once().await?;
+        Self::retry_call(client.timeouts().message_retries_count, events, |try_index: u8| {
+            let msg = Self::construct_deploy_message_json_with_clock(
                 params.clone(),
                 image.clone(),
                 key_pair,
                 workchain_id,
                 Some(client.timeouts()),
-                Some(try_index))?;
+                Some(try_index),
+                client)?;
 
-            Ok(Self::process_message(client, msg.message, msg.expire, try_index))
+            Ok(Self::process_message_with_events(client, msg.message, msg.expire, try_index, events))
         }).await
     }
 
@@ -488,17 +517,51 @@ impl Contract {
     // Asynchronously calls contract by sending given message.
     // To get calling result - need to load message,
     // it's id and processing status is returned by this function
-    pub async fn process_message(client: &NodeClient, msg: TvmMessage, expire: Option<u32>, try_index: u8)
-        -> Result<Transaction>
+    pub async fn process_message(
+        client: &NodeClient,
+        msg: TvmMessage,
+        expire: Option<u32>,
+        try_index: u8,
+    ) -> Result<Transaction>
+    {
+        Self::process_message_with_events(client, msg, expire, try_index, None).await
+    }
+
+    // Same as `process_message` reporting processing events to `events`
+    pub async fn process_message_with_events(
+        client: &NodeClient,
+        msg: TvmMessage,
+        expire: Option<u32>,
+        try_index: u8,
+        events: Option<&dyn ProcessingObserver>,
+    ) -> Result<Transaction>
     {
         let (data, msg_id) = Self::serialize_message(msg)?;
-        Self::process_serialized_message(client, &msg_id, &data, expire, try_index).await
+        Self::process_serialized_message_with_events(client, &msg_id, &data, expire, try_index, events).await
     }
 
-    pub async fn process_serialized_message(client: &NodeClient, id: &MessageId, msg: &[u8], expire: Option<u32>, try_index: u8) -> Result<Transaction> {
-        client.send_message(&id.to_bytes()?, msg).await?;
-        //println!("msg is sent, id: {}", id);
-        Self::wait_transaction_processing(client, id, expire, try_index).await
+    pub async fn process_serialized_message(
+        client: &NodeClient,
+        id: &MessageId,
+        msg: &[u8],
+        expire: Option<u32>,
+        try_index: u8,
+    ) -> Result<Transaction> {
+        Self::process_serialized_message_with_events(client, id, msg, expire, try_index, None).await
+    }
+
+    // Same as `process_serialized_message` reporting processing events to `events`
+    pub async fn process_serialized_message_with_events(
+        client: &NodeClient,
+        id: &MessageId,
+        msg: &[u8],
+        expire: Option<u32>,
+        try_index: u8,
+        events: Option<&dyn ProcessingObserver>,
+    ) -> Result<Transaction> {
+        MessageProcessing::new(id.clone(), msg.to_vec(), expire, try_index)
+            .run(client, events)
+            .await
     }
 
     pub async fn wait_transaction_processing(
@@ -508,83 +571,21 @@ impl Contract {
         try_index: u8,
     ) -> Result<Transaction>
     {
-        // timeout is growing from try to try
-        let mut timeout = Self::calc_timeout(
-            client.timeouts().message_processing_timeout,
-            client.timeouts().message_processing_timeout_grow_factor,
-            try_index);
-        if let Some(expire) = expire {
-            //println!("expire {}", expire);
-            let now = Self::now()?;
-            if expire <= now {
-                return Err(SdkError::InvalidArg { msg: "Message already expired".to_owned() }.into());
-            }
-            // timeout is time to `expire` plus additional time for masterblock awaiting
-            timeout = (expire - now) * 1000 + timeout;
-        }
-
-        let filter = json!({
-            "in_msg": { "eq": message_id.to_string() },
-            "status": { "eq": json_helper::transaction_status_to_u8(TransactionProcessingStatus::Finalized) }
-        }).to_string();
-
-        // main future awaiting message processing transaction
-        let transaction_future = async {
-            let transaction = client.wait_for(
-                TRANSACTIONS_TABLE_NAME,
-                &filter,
-                TRANSACTION_FIELDS_ORDINARY,
-                Some(timeout)).await?;
-
-            let transaction = serde_json::from_value::<Transaction>(transaction)?;
-            if transaction.compute.exit_code == Some(Self::MESSAGE_EXPIRED_CODE) ||
-                transaction.compute.exit_code == Some(Self::REPLAY_PROTECTION_CODE)
-            {
-                Err(SdkError::MessageExpired.into())
-            } else {
-                Ok(transaction)
-            }
-        };
+        Self::wait_transaction_processing_with_events(client, message_id, expire, try_index, None).await
+    }
 
-        // if message expiration time is set we make another future waiting for the masterchain
-        // block which reference to shadchain blocks generated after message expiration: it
-        // guarantees that message won't be processed by the contract later
-        if expire.is_some() {
-            let block_future = async {
-                let block = client.wait_for(
-                    BLOCKS_TABLE_NAME,
-                    &json!({
-                        "master": { "min_shard_gen_utime": { "ge": expire }}
-                    }).to_string(),
-                    "in_msg_descr { transaction_id }",
-                    Some(timeout)).await?;
-
-                // if we've recieved masterblock check that transactions from this block
-                // are already put into DB and there is no lag in transaction topic
-                match block["in_msg_descr"][0]["transaction_id"].as_str() {
-                    None => Err(SdkError::InvalidData {
-                        msg: "Invalid block recieved: no transaction ID".to_owned()
-                    }.into()),
-                    Some(string) => {
-                        client.wait_for(
-                            TRANSACTIONS_TABLE_NAME,
-                            &json!({ "id": { "eq": string }}).to_string(),
-                            "id",
-                            Some(timeout)).await?;
-
-                        Err(SdkError::MessageExpired.into())
-                    }
-                }
-            };
-            // awaiting the first future resolved
-            futures::pin_mut!(transaction_future, block_future);
-            futures::select! {
-                transaction = transaction_future.fuse() => transaction,
-                block = block_future.fuse() => block,
-            }
-        } else {
-            transaction_future.await
-        }
+    // Same as `wait_transaction_processing` reporting processing events to `events`
+    pub async fn wait_transaction_processing_with_events(
+        client: &NodeClient,
+        message_id: &MessageId,
+        expire: Option<u32>,
+        try_index: u8,
+        events: Option<&dyn ProcessingObserver>,
+    ) -> Result<Transaction>
+    {
+        MessageProcessing::sent(message_id.clone(), expire, try_index)
+            .run(client, events)
+            .await
     }
 }
 
@@ -678,6 +679,11 @@ impl Contract {
     /// Invokes local TVM instance with provided inbound message.
     /// Returns outbound messages generated by contract function and gas fee function consumed
     pub fn local_call_tvm(&self, message: TvmMessage) -> Result<Vec<Message>> {
+        self.local_call_tvm_with_clock(message, &SystemClock)
+    }
+
+    /// Same as `local_call_tvm` with the time taken from `clock`
+    pub fn local_call_tvm_with_clock(&self, message: TvmMessage, clock: &dyn Clock) -> Result<Vec<Message>> {
         let code = self.code.clone().ok_or(
             error!(SdkError::InvalidData { msg: "Account has no code".to_owned() }))?;
 
@@ -686,7 +692,7 @@ impl Contract {
             self.balance_other_as_hashmape()?,
             &self.id,
             None,
-            Self::now()?,
+            clock.now()?,
             code,
             self.data.clone(),
             &message)?;
@@ -707,6 +713,16 @@ impl Contract {
         &self,
         function_name: &str,
         input: Option<&Value>,
+    ) -> Result<Value> {
+        self.local_call_tvm_get_json_with_clock(function_name, input, &SystemClock)
+    }
+
+    /// Same as `local_call_tvm_get_json` with the time taken from `clock`
+    pub fn local_call_tvm_get_json_with_clock(
+        &self,
+        function_name: &str,
+        input: Option<&Value>,
+        clock: &dyn Clock,
     ) -> Result<Value> {
         let code = self.code.clone().ok_or(
             error!(SdkError::InvalidData { msg: "Account has no code".to_owned() }))?;
@@ -729,7 +745,7 @@ impl Contract {
             self.balance_other_as_hashmape()?,
             &self.id,
             None,
-            Self::now()?,
+            clock.now()?,
             code,
             self.data.clone(),
             stack_in)?;
@@ -742,12 +758,7 @@ impl Contract {
         abi: String, key_pair: Option<&Keypair>,
     ) -> Result<Vec<Message>>
     {
-        // pack params into bag of cells via ABI
-        let msg_body = ton_abi::encode_function_call(abi, func, header, input, false, key_pair)?;
-
-        let address = self.address();
-
-        let msg = Self::create_message(address, msg_body.into())?;
+        let msg = self.create_call_message_json(func, header, input, abi, key_pair)?;
 
         self.local_call_tvm(msg)
     }
@@ -756,12 +767,18 @@ impl Contract {
     /// Returns outbound messages generated by contract function and transaction fees
     #[cfg(feature = "fee_calculation")]
     pub fn local_call(&self, message: TvmMessage) -> Result<LocalCallResult> {
+        self.local_call_with_clock(message, &SystemClock)
+    }
+
+    /// Same as `local_call` with the time taken from `clock`
+    #[cfg(feature = "fee_calculation")]
+    pub fn local_call_with_clock(&self, message: TvmMessage, clock: &dyn Clock) -> Result<LocalCallResult> {
         // TODO: get real config
         let (tvm_messages, fees) = local_tvm::executor::call_executor(
             self.to_account()?,
             message,
             BlockchainConfig::default(),
-            Self::now()?)?;
+            clock.now()?)?;
 
         let mut messages = vec![];
         for tvm_msg in &tvm_messages {
@@ -777,14 +794,20 @@ impl Contract {
     pub fn local_call_json(&self, func: String, header: Option<String>, input: String, abi: String, key_pair: Option<&Keypair>)
         -> Result<LocalCallResult>
     {
-        // pack params into bag of cells via ABI
-        let msg_body = ton_abi::encode_function_call(abi, func, header, input, false, key_pair)?;
+        let msg = self.create_call_message_json(func, header, input, abi, key_pair)?;
 
-        let address = self.address();
+        self.local_call(msg)
+    }
 
-        let msg = Self::create_message(address, msg_body.into())?;
+    // Creates inbound message to this contract for local calls
+    pub(crate) fn create_call_message_json(&self, func: String, header: Option<String>, input: String,
+        abi: String, key_pair: Option<&Keypair>,
+    ) -> Result<TvmMessage>
+    {
+        // pack params into bag of cells via ABI
+        let msg_body = ton_abi::encode_function_call(abi, func, header, input, false, key_pair)?;
 
-        self.local_call(msg)
+        Self::create_message(self.address(), msg_body.into())
     }
 
     /// Decodes output parameters returned by contract function call
@@ -833,7 +856,7 @@ impl Contract {
 
     // Calculate timeout according to try number and timeout grow rate
     // (timeouts are growing from try to try)
-    fn calc_timeout(timeout: u32, grow_rate: f32, try_index: u8) -> u32 {
+    pub(crate) fn calc_timeout(timeout: u32, grow_rate: f32, try_index: u8) -> u32 {
         (timeout as f64 * grow_rate.powi(try_index as i32) as f64) as u32
     }
 
@@ -843,6 +866,7 @@ impl Contract {
         abi: String,
         header: Option<String>,
         try_index: Option<u8>,
+        clock: &dyn Clock,
     ) -> Result<(Option<String>, Option<u32>)> {
         let abi = AbiContract::load(abi.as_bytes())?;
         // use expire only if contract supports it
@@ -854,7 +878,7 @@ impl Contract {
                 timeouts.message_expiration_timeout,
                 timeouts.message_expiration_timeout_grow_factor,
                 try_index.unwrap_or(0));
-            let expire = Self::now()? + timeout / 1000;
+            let expire = clock.now()? + timeout / 1000;
             let expire = ton_abi::TokenValue::Expire(expire);
 
             let header = serde_json::from_str::<Value>(&header.unwrap_or("{}".to_owned()))?;
@@ -886,7 +910,21 @@ impl Contract {
         timeouts: Option<&TimeoutsConfig>,
         try_index: Option<u8>,
     ) -> Result<SdkMessage> {
-        let (header, expire) = Self::make_expire_header(timeouts, params.abi.clone(), params.header, try_index)?;
+        Self::construct_call_message_json_with_clock(
+            address, params, internal, key_pair, timeouts, try_index, &SystemClock)
+    }
+
+    // Same as `construct_call_message_json` with the expiration time counted by `clock`
+    pub fn construct_call_message_json_with_clock(
+        address: MsgAddressInt,
+        params: FunctionCallSet,
+        internal: bool,
+        key_pair: Option<&Keypair>,
+        timeouts: Option<&TimeoutsConfig>,
+        try_index: Option<u8>,
+        clock: &dyn Clock,
+    ) -> Result<SdkMessage> {
+        let (header, expire) = Self::make_expire_header(timeouts, params.abi.clone(), params.header, try_index, clock)?;
 
         // pack params into bag of cells via ABI
         let msg_body = ton_abi::encode_function_call(
@@ -907,6 +945,51 @@ impl Contract {
         Self::create_message(address, body_cell)
     }
 
+    // Creates internal message with the function call encoded by ABI as a body.
+    // Message without body is created if the call is not provided
+    pub fn construct_internal_message_json(
+        params: InternalMessageParams,
+        call: Option<FunctionCallSet>,
+    ) -> Result<TvmMessage> {
+        let body = match call {
+            Some(call) => Some(ton_abi::encode_function_call(
+                call.abi, call.func, call.header, call.input, true, None)?.into()),
+            None => None
+        };
+
+        Self::create_internal_message(params, body)
+    }
+
+    // Creates internal message with provided body
+    pub fn create_internal_message(params: InternalMessageParams, body: Option<SliceData>) -> Result<TvmMessage> {
+        let mut other = HashmapE::with_bit_len(32);
+        for currency in &params.other_currencies {
+            other.set(
+                currency.currency.write_to_new_cell()?.into(),
+                &ton_block::VarUInteger32::from(currency.value).write_to_new_cell()?.into())?;
+        }
+
+        let mut msg_header = InternalMessageHeader::default();
+        msg_header.src = match params.src {
+            Some(src) => MsgAddressIntOrNone::Some(src),
+            None => MsgAddressIntOrNone::None
+        };
+        msg_header.dst = params.dst;
+        msg_header.value = CurrencyCollection { grams: params.value.into(), other: other.into() };
+        msg_header.bounce = params.bounce;
+        msg_header.ihr_disabled = true;
+
+        let mut msg = TvmMessage::with_int_header(msg_header);
+        if let Some(state_init) = params.state_init {
+            msg.set_state_init(state_init);
+        }
+        if let Some(body) = body {
+            msg.set_body(body);
+        }
+
+        Ok(msg)
+    }
+
     // Packs given inputs by abi into Message struct without sign and returns data to sign.
     // Sign should be then added with `add_sign_to_message` function
     // Works with json representation of input and abi.
@@ -916,7 +999,18 @@ impl Contract {
         timeouts: Option<&TimeoutsConfig>,
         try_index: Option<u8>,
     ) -> Result<MessageToSign> {
-        let (header, expire) = Self::make_expire_header(timeouts, params.abi.clone(), params.header, try_index)?;
+        Self::get_call_message_bytes_for_signing_with_clock(address, params, timeouts, try_index, &SystemClock)
+    }
+
+    // Same as `get_call_message_bytes_for_signing` with the expiration time counted by `clock`
+    pub fn get_call_message_bytes_for_signing_with_clock(
+        address: MsgAddressInt,
+        params: FunctionCallSet,
+        timeouts: Option<&TimeoutsConfig>,
+        try_index: Option<u8>,
+        clock: &dyn Clock,
+    ) -> Result<MessageToSign> {
+        let (header, expire) = Self::make_expire_header(timeouts, params.abi.clone(), params.header, try_index, clock)?;
 
         // pack params into bag of cells via ABI
         let (msg_body, data_to_sign) = ton_abi::prepare_function_call_for_sign(
@@ -944,7 +1038,21 @@ impl Contract {
         timeouts: Option<&TimeoutsConfig>,
         try_index: Option<u8>,
     ) -> Result<SdkMessage> {
-        let (header, expire) = Self::make_expire_header(timeouts, params.abi.clone(), params.header, try_index)?;
+        Self::construct_deploy_message_json_with_clock(
+            params, image, key_pair, workchain_id, timeouts, try_index, &SystemClock)
+    }
+
+    // Same as `construct_deploy_message_json` with the expiration time counted by `clock`
+    pub fn construct_deploy_message_json_with_clock(
+        params: FunctionCallSet,
+        image: ContractImage,
+        key_pair: Option<&Keypair>,
+        workchain_id: i32,
+        timeouts: Option<&TimeoutsConfig>,
+        try_index: Option<u8>,
+        clock: &dyn Clock,
+    ) -> Result<SdkMessage> {
+        let (header, expire) = Self::make_expire_header(timeouts, params.abi.clone(), params.header, try_index, clock)?;
 
         let msg_body = ton_abi::encode_function_call(
             params.abi, params.func, header, params.input, false, key_pair)?;
@@ -985,7 +1093,20 @@ impl Contract {
         timeouts: Option<&TimeoutsConfig>,
         try_index: Option<u8>,
     ) -> Result<MessageToSign> {
-        let (header, expire) = Self::make_expire_header(timeouts, params.abi.clone(), params.header, try_index)?;
+        Self::get_deploy_message_bytes_for_signing_with_clock(
+            params, image, workchain_id, timeouts, try_index, &SystemClock)
+    }
+
+    // Same as `get_deploy_message_bytes_for_signing` with the expiration time counted by `clock`
+    pub fn get_deploy_message_bytes_for_signing_with_clock(
+        params: FunctionCallSet,
+        image: ContractImage,
+        workchain_id: i32,
+        timeouts: Option<&TimeoutsConfig>,
+        try_index: Option<u8>,
+        clock: &dyn Clock,
+    ) -> Result<MessageToSign> {
+        let (header, expire) = Self::make_expire_header(timeouts, params.abi.clone(), params.header, try_index, clock)?;
 
         let (msg_body, data_to_sign) = ton_abi::prepare_function_call_for_sign(
             params.abi, params.func, header, params.input)?;
@@ -1111,8 +1232,10 @@ impl Contract {
             &storage))
     }
 
+    // Current system time in seconds. Functions creating messages or running contracts
+    // locally take a `Clock` instead
     pub fn now() -> Result<u32> {
-        Ok(<u32>::try_from(Utc::now().timestamp())?)
+        SystemClock.now()
     }
 }
 
@@ -111,6 +111,27 @@ pub enum SdkError {
     #[fail(display = "Message expired")]
     MessageExpired,
 
+    #[fail(display = "Operation was cancelled")]
+    OperationCancelled,
+
+    #[fail(display = "Operation was not completed before the deadline")]
+    OperationTimeout,
+
+    #[fail(display = "Server belongs to another network: expected zerostate {}, server zerostate {}", expected, actual)]
+    WrongNetwork {
+        expected: String,
+        actual: String
+    },
+
+    #[fail(display = "Local clock differs from the server one by {} ms, allowed offset is {} ms", offset, max)]
+    ClockOutOfSync {
+        offset: i64,
+        max: u32
+    },
+
     #[fail(display = "SDK is initialized without node address")]
     SdkNotInitialized,
+
+    #[fail(display = "Subscription connection was restored, some updates could be missed")]
+    SubscriptionReconnected,
 }
@@ -66,6 +66,21 @@ pub fn deserialize_tree_of_cells_from_base64<'de, D>(b64: &str) -> Result<Cell,
         .map_err(|err| D::Error::custom(format!("BOC read error: {}", err)))
 }
 
+pub fn serialize_bytes_to_base64<S>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error>
+    where S: serde::Serializer
+{
+    s.serialize_str(&base64::encode(bytes))
+}
+
+pub fn deserialize_bytes_from_base64<'de, D>(d: D) -> Result<Vec<u8>, D::Error>
+    where D: serde::Deserializer<'de>
+{
+    let b64 = d.deserialize_string(StringVisitor)?;
+
+    base64::decode(&b64)
+        .map_err(|err| D::Error::custom(format!("error decode base64: {}", err)))
+}
+
 pub fn deserialize_tree_of_cells_opt_cell<'de, D>(d: D) -> Result<Option<Cell>, D::Error>
     where D: serde::Deserializer<'de>
 {
@@ -140,6 +155,14 @@ pub fn deserialize_acc_state_change<'de, D>(d: D) -> Result<AccStatusChange, D::
     }
 }
 
+pub fn acc_state_change_to_u8(change: AccStatusChange) -> u8 {
+    match change {
+        AccStatusChange::Unchanged => 0,
+        AccStatusChange::Frozen => 2,
+        AccStatusChange::Deleted => 3
+    }
+}
+
 pub fn deserialize_skipped_reason<'de, D>(d: D) -> Result<Option<ComputeSkipReason>, D::Error>
     where D: serde::Deserializer<'de>
 {
@@ -152,6 +175,14 @@ pub fn deserialize_skipped_reason<'de, D>(d: D) -> Result<Option<ComputeSkipReas
     }
 }
 
+pub fn skipped_reason_to_u8(reason: ComputeSkipReason) -> u8 {
+    match reason {
+        ComputeSkipReason::NoState => 0,
+        ComputeSkipReason::BadState => 1,
+        ComputeSkipReason::NoGas => 2
+    }
+}
+
 pub fn deserialize_message_type<'de, D>(d: D) -> Result<MessageType, D::Error>
     where D: serde::Deserializer<'de>
 {
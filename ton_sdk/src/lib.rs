@@ -34,8 +34,11 @@ pub use ton_abi::Function as AbiFunction;
 mod error;
 pub use error::SdkError;
 
+pub mod clock;
+pub use clock::{Clock, ManualClock, SystemClock};
+
 mod contract;
-pub use contract::{Contract, ContractImage, FunctionCallSet};
+pub use contract::{Contract, ContractImage, FunctionCallSet, InternalMessageParams, OtherCurrencyValue};
 
 mod message;
 pub use message::{Message, MessageId, MessageType};
@@ -51,22 +54,63 @@ pub use transaction::{Transaction, TransactionId};
 
 pub mod types;
 pub use types::{NodeClientConfig, TimeoutsConfig};
+#[cfg(feature = "node_interaction")]
+pub use types::{RetryConfig, ServerInfo};
 
 #[cfg(feature = "node_interaction")]
 pub mod node_client;
 #[cfg(feature = "node_interaction")]
-pub use node_client::OrderBy;
+pub use node_client::{OrderBy, SortDirection};
+
+#[cfg(feature = "node_interaction")]
+pub mod query_builder;
+
+#[cfg(feature = "node_interaction")]
+pub mod transport;
+#[cfg(feature = "node_interaction")]
+pub use transport::Transport;
+
+#[cfg(feature = "node_interaction")]
+pub mod memory_transport;
+
+#[cfg(feature = "node_interaction")]
+mod records_cache;
+
+#[cfg(feature = "node_interaction")]
+pub mod metrics;
+#[cfg(feature = "node_interaction")]
+pub use metrics::{MetricsObserver, OperationMetrics};
+
+#[cfg(feature = "node_interaction")]
+pub mod cancellation;
+#[cfg(feature = "node_interaction")]
+pub use cancellation::{with_deadline, CancellationToken};
+
+#[cfg(feature = "node_interaction")]
+pub mod processing;
+#[cfg(feature = "node_interaction")]
+pub use processing::{
+    processing_events, MessageProcessing, ProcessingEvent, ProcessingObserver, ProcessingState};
+
+#[cfg(feature = "local_server")]
+pub mod local_server;
 pub use node_client::NodeClient;
 
 #[cfg(not(feature = "node_interaction"))]
 pub mod node_client {
-    use crate::{NodeClientConfig, TimeoutsConfig};
+    use crate::{Clock, NodeClientConfig, SystemClock, TimeoutsConfig};
     use ton_types::Result;
 
     pub struct NodeClient {
         timeouts: TimeoutsConfig
     }
 
+    impl Clock for NodeClient {
+        fn now_ms(&self) -> i64 {
+            SystemClock.now_ms()
+        }
+    }
+
     impl NodeClient {
         // Globally initializes client with server address
         pub fn new(config: NodeClientConfig) -> Result<NodeClient> {
@@ -0,0 +1,870 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+// Local stand-in for the GraphQL server used in integration tests. It serves the subset
+// of the schema used by the SDK: `accounts`, `messages`, `transactions` and `blocks`
+// queries and subscriptions and the `postRequests` mutation. Records are kept in
+// `InMemoryTransport` and posted messages are executed by the local transaction executor
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::SdkError;
+use crate::json_helper;
+use crate::local_tvm;
+use crate::memory_transport::{InMemoryTransport, TABLES};
+use crate::transport::{QueryParams, RecordStream, Transport};
+use crate::types::{BLOCKS_TABLE_NAME, CONTRACTS_TABLE_NAME, MESSAGES_TABLE_NAME, TRANSACTIONS_TABLE_NAME};
+use crate::{Contract, MessageId, TransactionId};
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, AbortHandle};
+use futures::{SinkExt, StreamExt};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::WebSocketStream;
+use ton_block::{
+    Account, CommonMsgInfo, GetRepresentationHash, InternalMessageHeader, Message as TvmMessage,
+    MsgAddressInt, MsgAddressIntOrNone, Serializable, TrComputePhase, TransactionDescr,
+    TransactionProcessingStatus};
+use ton_executor::BlockchainConfig;
+use ton_types::cells_serialization::BagOfCells;
+use ton_types::{Cell, Result};
+
+// Address of the giver contract as it is in the TON OS startup edition
+pub const GIVER_ADDRESS: &str = "0:841288ed3b55d9cdafa806807f02a0ae0c169aa5edfe88a789a6482429756a94";
+
+pub const GIVER_ABI: &str = r#"{
+    "ABI version": 1,
+    "functions": [
+        {
+            "name": "constructor",
+            "inputs": [],
+            "outputs": []
+        },
+        {
+            "name": "sendGrams",
+            "inputs": [
+                {"name":"dest","type":"address"},
+                {"name":"amount","type":"uint64"}
+            ],
+            "outputs": []
+        }
+    ],
+    "events": [],
+    "data": []
+}"#;
+
+// `sendTransaction` of the multisig wallet is accepted by the giver as well, so wallet
+// transfers can be sent from the giver address
+const WALLET_ABI: &str = r#"{
+    "ABI version": 2,
+    "header": ["pubkey", "time", "expire"],
+    "functions": [
+        {
+            "name": "sendTransaction",
+            "inputs": [
+                {"name":"dest","type":"address"},
+                {"name":"value","type":"uint128"},
+                {"name":"bounce","type":"bool"},
+                {"name":"flags","type":"uint8"},
+                {"name":"payload","type":"cell"}
+            ],
+            "outputs": []
+        }
+    ],
+    "events": [],
+    "data": []
+}"#;
+
+// Limits the number of internal messages delivered as a result of one posted message
+const MAX_DELIVERED_MESSAGES: usize = 100;
+
+// Logical time distance between executed transactions
+const LT_STEP: u64 = 1_000;
+
+// Interval of `ka` frames sent to subscription clients
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Delay before the next connection is accepted after the accept error
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+// Local GraphQL server running in its own thread. Server is stopped when dropped
+pub struct LocalServer {
+    address: SocketAddr,
+    transport: InMemoryTransport,
+    stop: Option<oneshot::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl LocalServer {
+    // Starts server on a free local port. Posted messages are executed by the local executor,
+    // messages to the `GIVER_ADDRESS` are handled by the built-in giver
+    pub fn start() -> Result<Self> {
+        Self::start_with_clock(Arc::new(SystemClock))
+    }
+
+    // Starts server executing messages at the time of the given clock, e.g. to make
+    // messages expired by advancing `ManualClock`
+    pub fn start_with_clock(clock: Arc<dyn Clock>) -> Result<Self> {
+        let transport = InMemoryTransport::new();
+        let executor = LocalExecutor::new(clock)?;
+        transport.set_message_processor(move |id, body| executor.process(id, body));
+
+        Self::with_transport(transport)
+    }
+
+    // Starts server serving records of the given transport
+    pub fn with_transport(transport: InMemoryTransport) -> Result<Self> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let (stop, stopped) = oneshot::channel();
+        let (started_sender, started) = std::sync::mpsc::channel();
+        let server_transport = transport.clone();
+        let thread = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new()
+                .basic_scheduler()
+                .enable_io()
+                .enable_time()
+                .build();
+            let mut runtime = match runtime {
+                Ok(runtime) => runtime,
+                Err(err) => {
+                    let _ = started_sender.send(Err(err));
+                    return;
+                }
+            };
+            runtime.block_on(async move {
+                let mut listener = match tokio::net::TcpListener::from_std(listener) {
+                    Ok(listener) => {
+                        let _ = started_sender.send(Ok(()));
+                        listener
+                    },
+                    Err(err) => {
+                        let _ = started_sender.send(Err(err));
+                        return;
+                    }
+                };
+                let accept = async move {
+                    loop {
+                        match listener.accept().await {
+                            Ok((stream, _)) => {
+                                tokio::spawn(handle_connection(stream, server_transport.clone()));
+                            },
+                            // e.g. too many open files: wait for connections to be closed
+                            Err(_) => tokio::time::delay_for(ACCEPT_RETRY_DELAY).await
+                        }
+                    }
+                };
+                futures::pin_mut!(accept);
+                future::select(accept, stopped).await;
+            })
+        });
+
+        started
+            .recv()
+            .map_err(|_| SdkError::InternalError { msg: "Local server thread is finished".to_owned() })??;
+
+        Ok(Self { address, transport, stop: Some(stop), thread: Some(thread) })
+    }
+
+    // Server address to be used as `base_url` of the client config
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    // Transport keeping server records. Can be used to add or inspect records directly
+    pub fn transport(&self) -> &InMemoryTransport {
+        &self.transport
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Serves one client connection: either single HTTP request or graphql-ws session
+async fn handle_connection(mut stream: TcpStream, transport: InMemoryTransport) -> Result<()> {
+    let mut buffer = Vec::new();
+    let head_length = loop {
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+        let mut chunk = [0u8; 4096];
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..head_length]).to_string();
+    let mut lines = head.split("\r\n");
+    let method = lines.next().and_then(|line| line.split_whitespace().next()).unwrap_or("").to_owned();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            Some((parts.next()?.trim().to_lowercase(), parts.next()?.trim().to_owned()))
+        })
+        .collect();
+
+    if headers.get("upgrade").map(|value| value.eq_ignore_ascii_case("websocket")).unwrap_or(false) {
+        // handshake is done by the websocket library so it has to read request head again
+        let socket = tokio_tungstenite::accept_hdr_async(
+            PrefixedStream { prefix: buffer, position: 0, stream },
+            add_protocol_header).await?;
+        serve_subscriptions(socket, transport).await;
+        return Ok(());
+    }
+
+    let content_length = headers.get("content-length").and_then(|value| value.parse().ok()).unwrap_or(0);
+    let mut body = buffer.split_off(head_length);
+    while body.len() < content_length {
+        let mut chunk = [0u8; 4096];
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    let (status, response) = if method == "POST" {
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(Value::Array(requests)) => {
+                let results = future::join_all(requests.iter().map(|request| execute(&transport, request)));
+                ("200 OK", Value::Array(results.await))
+            },
+            Ok(request) => ("200 OK", execute(&transport, &request).await),
+            Err(err) => ("400 Bad Request", error_response("BAD_USER_INPUT", format!("Invalid request body: {}", err)))
+        }
+    } else {
+        ("400 Bad Request", error_response("BAD_USER_INPUT", "Only POST requests are supported".to_owned()))
+    };
+
+    let response = response.to_string();
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        response.len());
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.as_bytes()).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+fn add_protocol_header(_request: &Request, mut response: Response)
+    -> std::result::Result<Response, ErrorResponse>
+{
+    response.headers_mut().insert("Sec-WebSocket-Protocol", HeaderValue::from_static("graphql-ws"));
+    Ok(response)
+}
+
+// Error codes are the ones Apollo server uses
+fn error_response(code: &str, message: String) -> Value {
+    json!({ "errors": [{ "message": message, "extensions": { "code": code } }] })
+}
+
+// GraphQL operation of the form used by the SDK: `query accounts(...) { accounts(...) { id } }`.
+// Operation arguments are taken from the variables, inline arguments are not supported
+struct Operation {
+    kind: String,
+    field: String,
+    selection: String,
+}
+
+fn parse_operation(query: &str) -> Result<Operation> {
+    let invalid = || SdkError::InvalidArg { msg: format!("Unsupported GraphQL query: {}", query) };
+
+    let kind = query.trim_start().split(|c: char| !c.is_alphanumeric()).next().unwrap_or("").to_owned();
+
+    let body = &query[query.find('{').ok_or_else(invalid)? + 1..];
+    let field_end = body.find(|c: char| c == '(' || c == '{' || c == '}').unwrap_or(body.len());
+    let field = body[..field_end].trim().to_owned();
+
+    let rest = &body[field_end..];
+    let selection = match rest.find('{') {
+        Some(open) => {
+            let mut depth = 0;
+            let mut close = None;
+            for (index, c) in rest[open..].char_indices() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            close = Some(open + index);
+                            break;
+                        }
+                    },
+                    _ => {}
+                }
+            }
+            rest[open + 1..close.ok_or_else(invalid)?].trim().to_owned()
+        },
+        None => String::new()
+    };
+
+    Ok(Operation { kind, field, selection })
+}
+
+// Variables can be passed either as JSON object or as a string containing it
+fn variables(request: &Value) -> Value {
+    match &request["variables"] {
+        Value::String(string) => serde_json::from_str(string).unwrap_or(Value::Null),
+        value => value.clone()
+    }
+}
+
+fn query_params(operation: &Operation, variables: &Value) -> Result<QueryParams> {
+    let order_by = match &variables["orderBy"] {
+        Value::Null => None,
+        // only the first ordering field is supported
+        Value::Array(items) => items.first().cloned(),
+        item => Some(item.clone())
+    };
+
+    Ok(QueryParams {
+        table: operation.field.clone(),
+        filter: variables["filter"].clone(),
+        fields: operation.selection.clone(),
+        order_by: order_by.map(serde_json::from_value).transpose()?,
+        limit: variables["limit"].as_u64().map(|limit| limit as u32),
+        timeout: variables["timeout"].as_f64().map(|timeout| timeout as u32),
+    })
+}
+
+async fn execute(transport: &InMemoryTransport, request: &Value) -> Value {
+    match execute_operation(transport, request).await {
+        Ok(data) => json!({ "data": data }),
+        Err(err) => error_response("GRAPHQL_VALIDATION_FAILED", err.to_string())
+    }
+}
+
+async fn execute_operation(transport: &InMemoryTransport, request: &Value) -> Result<Value> {
+    let query = request["query"].as_str()
+        .ok_or(SdkError::InvalidArg { msg: "Query is missing".to_owned() })?;
+    let operation = parse_operation(query)?;
+    let variables = variables(request);
+
+    let result = match (operation.kind.as_str(), operation.field.as_str()) {
+        ("query", table) if TABLES.contains(&table) =>
+            transport.query(&query_params(&operation, &variables)?).await?,
+        ("query", "info") => match transport.server_info().await?.version {
+            Some(version) => json!({ "version": version, "time": transport.server_time().await? }),
+            None => bail!(SdkError::InvalidArg { msg: "Unsupported operation: query info".to_owned() })
+        },
+        ("query", "__schema") => {
+            let fields: Vec<Value> = transport.server_info().await?.features
+                .into_iter()
+                .map(|name| json!({ "name": name }))
+                .collect();
+            json!({ "queryType": { "fields": fields } })
+        },
+        ("mutation", "postRequests") => {
+            for request in variables["requests"].as_array().unwrap_or(&Vec::new()) {
+                let id = base64::decode(request["id"].as_str().unwrap_or(""))?;
+                let body = base64::decode(request["body"].as_str().unwrap_or(""))?;
+                transport.send_message(&id, &body).await?;
+            }
+            Value::Null
+        },
+        (kind, field) => bail!(SdkError::InvalidArg {
+            msg: format!("Unsupported operation: {} {}", kind, field)
+        })
+    };
+
+    let mut data = Map::new();
+    data.insert(operation.field, result);
+    Ok(Value::Object(data))
+}
+
+async fn subscribe(transport: &InMemoryTransport, payload: &Value) -> Result<(String, RecordStream)> {
+    let query = payload["query"].as_str()
+        .ok_or(SdkError::InvalidArg { msg: "Query is missing".to_owned() })?;
+    let operation = parse_operation(query)?;
+    if operation.kind != "subscription" || !TABLES.contains(&operation.field.as_str()) {
+        bail!(SdkError::InvalidArg {
+            msg: format!("Unsupported subscription: {} {}", operation.kind, operation.field)
+        });
+    }
+
+    let variables = variables(payload);
+    let stream = transport.subscribe(&operation.field, &variables["filter"], &operation.selection).await?;
+
+    Ok((operation.field, stream))
+}
+
+fn data_frame(id: &Value, table: &str, record: Value) -> WsMessage {
+    let mut data = Map::new();
+    data.insert(table.to_owned(), record);
+
+    WsMessage::Text(json!({ "type": "data", "id": id, "payload": { "data": data } }).to_string())
+}
+
+fn error_frame(id: &Value, message: String) -> WsMessage {
+    WsMessage::Text(json!({ "type": "error", "id": id, "payload": { "message": message } }).to_string())
+}
+
+// Sends `ka` frames until the session is closed. The first one is sent immediately
+async fn keep_alive(sender: mpsc::UnboundedSender<WsMessage>) {
+    let mut interval = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if sender.unbounded_send(WsMessage::Text(json!({ "type": "ka" }).to_string())).is_err() {
+            break;
+        }
+    }
+}
+
+// Serves graphql-ws protocol session
+async fn serve_subscriptions(socket: WebSocketStream<PrefixedStream>, transport: InMemoryTransport) {
+    let (mut sink, mut source) = socket.split();
+    let (sender, mut receiver) = mpsc::unbounded::<WsMessage>();
+
+    let writer = async move {
+        while let Some(message) = receiver.next().await {
+            if sink.send(message).await.is_err() {
+                break;
+            }
+        }
+    };
+
+    let reader = async move {
+        let mut subscriptions: HashMap<String, AbortHandle> = HashMap::new();
+        let mut keep_alive_handle: Option<AbortHandle> = None;
+        while let Some(Ok(message)) = source.next().await {
+            let message: Value = match message {
+                WsMessage::Text(text) => match serde_json::from_str(&text) {
+                    Ok(message) => message,
+                    Err(err) => {
+                        let _ = sender.unbounded_send(
+                            error_frame(&Value::Null, format!("Invalid message: {}", err)));
+                        continue;
+                    }
+                },
+                WsMessage::Close(_) => break,
+                _ => continue
+            };
+
+            let id = message["id"].clone();
+            match message["type"].as_str() {
+                Some("connection_init") => {
+                    let _ = sender.unbounded_send(
+                        WsMessage::Text(json!({ "type": "connection_ack" }).to_string()));
+                    if keep_alive_handle.is_none() {
+                        let (future, handle) = future::abortable(keep_alive(sender.clone()));
+                        tokio::spawn(future);
+                        keep_alive_handle = Some(handle);
+                    }
+                },
+                Some("start") => match subscribe(&transport, &message["payload"]).await {
+                    Ok((table, stream)) => {
+                        let frame_sender = sender.clone();
+                        let frame_id = id.clone();
+                        let forward = stream.for_each(move |record| {
+                            let frame = match record {
+                                Ok(record) => data_frame(&frame_id, &table, record),
+                                Err(err) => error_frame(&frame_id, err.to_string())
+                            };
+                            let _ = frame_sender.unbounded_send(frame);
+                            future::ready(())
+                        });
+                        let (forward, handle) = future::abortable(forward);
+                        tokio::spawn(forward);
+                        subscriptions.insert(id.to_string(), handle);
+                    },
+                    Err(err) => {
+                        let _ = sender.unbounded_send(error_frame(&id, err.to_string()));
+                    }
+                },
+                Some("stop") => {
+                    if let Some(handle) = subscriptions.remove(&id.to_string()) {
+                        handle.abort();
+                    }
+                    let _ = sender.unbounded_send(
+                        WsMessage::Text(json!({ "type": "complete", "id": id }).to_string()));
+                },
+                Some("connection_terminate") => break,
+                _ => {
+                    let _ = sender.unbounded_send(error_frame(&id, format!("Unsupported message: {}", message)));
+                }
+            }
+        }
+
+        for (_, handle) in subscriptions {
+            handle.abort();
+        }
+        if let Some(handle) = keep_alive_handle {
+            handle.abort();
+        }
+    };
+
+    futures::pin_mut!(reader, writer);
+    future::select(reader, writer).await;
+}
+
+// Socket returning already read bytes before reading from the socket itself
+struct PrefixedStream {
+    prefix: Vec<u8>,
+    position: usize,
+    stream: TcpStream,
+}
+
+impl AsyncRead for PrefixedStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.position < this.prefix.len() {
+            let count = std::cmp::min(buf.len(), this.prefix.len() - this.position);
+            buf[..count].copy_from_slice(&this.prefix[this.position..this.position + count]);
+            this.position += count;
+            return Poll::Ready(Ok(count));
+        }
+        Pin::new(&mut this.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for PrefixedStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+// Records produced by a message processing and internal messages to be delivered
+type ProcessingResult = (Vec<(String, Value)>, Vec<TvmMessage>);
+
+// Executes posted messages keeping account states. Internal messages produced by
+// transactions are delivered to destination accounts in the same way
+struct LocalExecutor {
+    accounts: Mutex<HashMap<String, Cell>>,
+    giver: MsgAddressInt,
+    last_lt: AtomicU64,
+    clock: Arc<dyn Clock>,
+    // referenced by master blocks of messages which produced no transactions
+    last_transaction: Mutex<Option<String>>,
+}
+
+impl LocalExecutor {
+    fn new(clock: Arc<dyn Clock>) -> Result<Self> {
+        Ok(Self {
+            accounts: Mutex::new(HashMap::new()),
+            giver: MsgAddressInt::from_str(GIVER_ADDRESS)?,
+            last_lt: AtomicU64::new(0),
+            clock,
+            last_transaction: Mutex::new(None),
+        })
+    }
+
+    fn process(&self, id: &str, body: &[u8]) -> Vec<(String, Value)> {
+        let message = match Contract::deserialize_message(body) {
+            Ok(message) => message,
+            Err(_) => return Vec::new()
+        };
+
+        let mut records = Vec::new();
+        if let Ok(record) = message_record(&message) {
+            records.push((MESSAGES_TABLE_NAME.to_owned(), record));
+        }
+
+        let mut queue = VecDeque::new();
+        queue.push_back((id.to_owned(), message));
+        let mut delivered = 0;
+        while let Some((id, message)) = queue.pop_front() {
+            let result = match destination(&message) {
+                Some(address) if address == self.giver => self.giver_transaction(&id, &message),
+                Some(address) => self.execute(&id, address, &message),
+                None => continue
+            };
+
+            // like the real node does, message which is not accepted by the contract
+            // is dropped without transaction
+            if let Ok((processing_records, internal_messages)) = result {
+                records.extend(processing_records);
+                for message in internal_messages {
+                    delivered += 1;
+                    if delivered > MAX_DELIVERED_MESSAGES {
+                        break;
+                    }
+                    if let Ok(id) = message.hash() {
+                        queue.push_back((MessageId::from(&id.as_slice()[..]).to_string(), message));
+                    }
+                }
+            }
+        }
+
+        let transactions = records
+            .iter()
+            .filter(|(table, _)| table == TRANSACTIONS_TABLE_NAME)
+            .map(|(_, transaction)| json!({
+                "msg_id": transaction["in_msg"],
+                "transaction_id": transaction["id"],
+            }))
+            .collect();
+        if let Some(block) = self.master_block(id, transactions) {
+            records.push((BLOCKS_TABLE_NAME.to_owned(), block));
+        }
+
+        records
+    }
+
+    // Every posted message is followed by the master block, so the block generated after
+    // the message expiration time proves that the message is not going to be processed
+    fn master_block(&self, id: &str, mut in_msg_descr: Vec<Value>) -> Option<Value> {
+        let mut last_transaction = self.last_transaction.lock().unwrap();
+        match in_msg_descr.last() {
+            Some(descr) => *last_transaction = descr["transaction_id"].as_str().map(str::to_owned),
+            None => in_msg_descr.push(json!({ "transaction_id": last_transaction.clone()? }))
+        }
+
+        let now = self.clock.now().ok()?;
+        Some(json!({
+            "id": hex::encode(Sha256::digest(format!("master block {}", id).as_bytes())),
+            "workchain_id": -1,
+            "gen_utime": now,
+            "master": { "min_shard_gen_utime": now },
+            "in_msg_descr": in_msg_descr,
+        }))
+    }
+
+    fn execute(&self, id: &str, address: MsgAddressInt, message: &TvmMessage) -> Result<ProcessingResult> {
+        let key = address.to_string();
+        let mut accounts = self.accounts.lock().unwrap();
+        let mut acc_root = match accounts.get(&key) {
+            Some(acc_root) => acc_root.clone(),
+            None => Account::default().write_to_new_cell()?.into()
+        };
+
+        let now = self.clock.now()?;
+        let block_lt = self.last_lt.fetch_add(LT_STEP, Ordering::SeqCst) + LT_STEP;
+        let transaction = local_tvm::executor::execute_message(
+            &mut acc_root, message, BlockchainConfig::default(), now, block_lt)?;
+        accounts.insert(key, acc_root.clone());
+
+        let mut out_messages = Vec::new();
+        transaction.iterate_out_msgs(&mut |message| {
+            out_messages.push(message);
+            Ok(true)
+        })?;
+
+        let mut records = Vec::new();
+        let mut out_ids = Vec::new();
+        for message in &out_messages {
+            let record = message_record(message)?;
+            out_ids.push(record["id"].clone());
+            records.push((MESSAGES_TABLE_NAME.to_owned(), record));
+        }
+
+        // account is added before the transaction so it is updated when transaction is found
+        if let Ok(contract) = Contract::from_cells(acc_root.into()) {
+            records.push((CONTRACTS_TABLE_NAME.to_owned(), account_record(contract)?));
+        }
+
+        let transaction_id = TransactionId::from(&transaction.hash()?.as_slice()[..]);
+        let mut record = json!({
+            "id": transaction_id.to_string(),
+            "status": json_helper::transaction_status_to_u8(TransactionProcessingStatus::Finalized),
+            "now": now,
+            "account_addr": address.to_string(),
+            "in_msg": id,
+            "block_id": format!("{:064x}", block_lt),
+            "out_msgs": out_ids,
+        });
+        if let TransactionDescr::Ordinary(descr) = transaction.read_description()? {
+            record["aborted"] = descr.aborted.into();
+            record["compute"] = match descr.compute_ph {
+                TrComputePhase::Vm(phase) => json!({
+                    "compute_type": 1,
+                    "success": phase.success,
+                    "exit_code": phase.exit_code
+                }),
+                TrComputePhase::Skipped(skipped) => json!({
+                    "compute_type": 0,
+                    "skipped_reason": json_helper::skipped_reason_to_u8(skipped.reason)
+                })
+            };
+            if let Some(storage) = descr.storage_ph {
+                record["storage"] = json!({
+                    "status_change": json_helper::acc_state_change_to_u8(storage.status_change)
+                });
+            }
+            if let Some(action) = descr.action {
+                record["action"] = json!({
+                    "success": action.success,
+                    "valid": action.valid,
+                    "no_funds": action.no_funds,
+                    "result_code": action.result_code
+                });
+            }
+        }
+        records.push((TRANSACTIONS_TABLE_NAME.to_owned(), record));
+
+        let internal_messages = out_messages
+            .into_iter()
+            .filter(|message| match message.header() {
+                CommonMsgInfo::IntMsgInfo(_) => true,
+                _ => false
+            })
+            .collect();
+
+        Ok((records, internal_messages))
+    }
+
+    // Giver has no code: `sendGrams` or wallet `sendTransaction` call is decoded and
+    // the internal message with requested value is sent to the destination. Payload
+    // of the wallet transfer is not attached
+    fn giver_transaction(&self, id: &str, message: &TvmMessage) -> Result<ProcessingResult> {
+        let body = message.body()
+            .ok_or(SdkError::InvalidData { msg: "Giver message has no body".to_owned() })?;
+        let call = Contract::decode_unknown_function_call_json(GIVER_ABI.to_owned(), body.clone(), false)
+            .or_else(|_| Contract::decode_unknown_function_call_json(WALLET_ABI.to_owned(), body, false))?;
+
+        let params: Value = serde_json::from_str(&call.params)?;
+        let (amount, bounce) = match call.function_name.as_str() {
+            "sendGrams" => (&params["amount"], false),
+            "sendTransaction" => (&params["value"], params["bounce"].as_bool().unwrap_or(true)),
+            function => bail!(SdkError::InvalidArg { msg: format!("Unknown giver function {}", function) })
+        };
+        let dest = MsgAddressInt::from_str(params["dest"].as_str().unwrap_or(""))?;
+        let amount = parse_amount(amount)
+            .ok_or(SdkError::InvalidArg { msg: format!("Invalid giver amount: {}", amount) })?;
+
+        let mut header = InternalMessageHeader::default();
+        header.src = MsgAddressIntOrNone::Some(self.giver.clone());
+        header.dst = dest;
+        header.bounce = bounce;
+        header.value.grams = amount.into();
+        let internal_message = TvmMessage::with_int_header(header);
+
+        let message_record = message_record(&internal_message)?;
+        let transaction = json!({
+            "id": hex::encode(Sha256::digest(id.as_bytes())),
+            "status": json_helper::transaction_status_to_u8(TransactionProcessingStatus::Finalized),
+            "now": self.clock.now()?,
+            "account_addr": GIVER_ADDRESS,
+            "in_msg": id,
+            "block_id": hex::encode(Sha256::digest(format!("block {}", id).as_bytes())),
+            "out_msgs": [message_record["id"]],
+            "aborted": false,
+            "compute": {
+                "compute_type": 1,
+                "exit_code": 0,
+                "success": true
+            }
+        });
+
+        Ok((
+            vec![
+                (MESSAGES_TABLE_NAME.to_owned(), message_record),
+                (TRANSACTIONS_TABLE_NAME.to_owned(), transaction),
+            ],
+            vec![internal_message]
+        ))
+    }
+}
+
+fn parse_amount(value: &Value) -> Option<u128> {
+    match value {
+        Value::String(string) if string.starts_with("0x") => u128::from_str_radix(&string[2..], 16).ok(),
+        Value::String(string) => string.parse().ok(),
+        value => value.as_u64().map(|value| value as u128)
+    }
+}
+
+fn destination(message: &TvmMessage) -> Option<MsgAddressInt> {
+    match message.header() {
+        CommonMsgInfo::IntMsgInfo(header) => Some(header.dst.clone()),
+        CommonMsgInfo::ExtInMsgInfo(header) => Some(header.dst.clone()),
+        CommonMsgInfo::ExtOutMsgInfo(_) => None
+    }
+}
+
+fn cell_to_base64(cell: &Cell) -> Result<String> {
+    let mut data = Vec::new();
+    BagOfCells::with_root(cell).write_to(&mut data, false)?;
+    Ok(base64::encode(&data))
+}
+
+fn message_record(message: &TvmMessage) -> Result<Value> {
+    let (boc, id) = Contract::serialize_message(message.clone())?;
+    let msg_type = match message.header() {
+        CommonMsgInfo::IntMsgInfo(_) => 0,
+        CommonMsgInfo::ExtInMsgInfo(_) => 1,
+        CommonMsgInfo::ExtOutMsgInfo(_) => 2
+    };
+    let body = match message.body() {
+        Some(body) => Some(cell_to_base64(&body.into_cell())?),
+        None => None
+    };
+
+    Ok(json!({
+        "id": id.to_string(),
+        "msg_type": msg_type,
+        // finalized
+        "status": 5,
+        "dst": destination(message).map(|address| address.to_string()),
+        "body": body,
+        "boc": base64::encode(&boc)
+    }))
+}
+
+fn account_record(contract: Contract) -> Result<Value> {
+    let code = match &contract.code {
+        Some(code) => Some(cell_to_base64(code)?),
+        None => None
+    };
+    let data = match &contract.data {
+        Some(data) => Some(cell_to_base64(data)?),
+        None => None
+    };
+
+    Ok(json!({
+        "id": contract.id.to_string(),
+        "acc_type": json_helper::account_status_to_u8(contract.acc_type),
+        "balance": format!("0x{:x}", contract.balance),
+        "code": code,
+        "data": data,
+        "last_paid": contract.last_paid
+    }))
+}
+
+#[cfg(test)]
+#[path = "tests/test_local_server.rs"]
+mod tests;
@@ -141,6 +141,7 @@ pub mod executor {
         TransactionDescr,
         TrComputePhase,
         Serializable,
+        Transaction,
     };
 
     #[derive(Default, Debug)]
@@ -158,21 +159,27 @@ pub mod executor {
             .ok_or(SdkError::LocalCallError { msg: "Cannot convert rams value".to_owned() }.into())
     }
 
+    // Executes message on the account state. `acc_root` is replaced with the new account state
+    pub(crate) fn execute_message(
+        acc_root: &mut Cell, msg: &Message, config: BlockchainConfig, timestamp: u32, block_lt: u64
+    ) -> Result<Transaction> {
+        let lt = Arc::new(std::sync::atomic::AtomicU64::new(block_lt + 1));
+        let executor = OrdinaryTransactionExecutor::new(config);
+        executor.execute(
+            Some(msg),
+            acc_root,
+            timestamp,
+            block_lt,
+            lt,
+            false)
+    }
+
     pub(crate) fn call_executor(account: Account, msg: Message, config: BlockchainConfig, timestamp: u32)
         -> Result<(Vec<Message>, TransactionFees)>
     {
         let mut acc_root = account.write_to_new_cell()?.into();
 
-        let block_lt = 1_000_000;
-        let lt = Arc::new(std::sync::atomic::AtomicU64::new(block_lt + 1));
-        let executor = OrdinaryTransactionExecutor::new(config);
-        let transaction = executor.execute(
-            Some(&msg),
-            &mut acc_root,
-            timestamp,
-            block_lt,
-            lt.clone(),
-            false)?;
+        let transaction = execute_message(&mut acc_root, &msg, config, timestamp, 1_000_000)?;
 
         let mut fees = TransactionFees::default();
 
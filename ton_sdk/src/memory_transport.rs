@@ -0,0 +1,404 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use crate::error::SdkError;
+use crate::json_helper;
+use crate::node_client::SortDirection;
+use crate::transport::{network_block_query, network_identity, QueryParams, RecordStream, Transport, TransportFuture};
+use crate::types::{ServerInfo, MESSAGES_TABLE_NAME, TRANSACTIONS_TABLE_NAME};
+use futures::channel::mpsc;
+use futures::StreamExt;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use ton_block::TransactionProcessingStatus;
+use ton_types::Result;
+
+// Same default page size as the GraphQL server has
+const DEFAULT_LIMIT: u32 = 50;
+
+pub const TABLES: [&str; 4] = ["accounts", "messages", "transactions", "blocks"];
+
+// Version of the GraphQL server the transport behaves like
+pub const SERVER_VERSION: &str = "0.27.0";
+
+// Called for every message posted to the transport. Returns records (table name and record)
+// to be added to the store as a result of message processing
+pub type MessageProcessor = dyn Fn(&str, &[u8]) -> Vec<(String, Value)> + Send + Sync;
+
+struct Listener {
+    table: String,
+    filter: Value,
+    sender: mpsc::UnboundedSender<Value>,
+}
+
+#[derive(Default)]
+struct Store {
+    tables: HashMap<String, Vec<Value>>,
+    listeners: Vec<Listener>,
+    sent_messages: Vec<(String, Vec<u8>)>,
+    processor: Option<Arc<MessageProcessor>>,
+    legacy_server: bool,
+    subscriptions_disabled: bool,
+    // Difference in ms between the emulated server clock and the local one
+    time_offset: i64,
+}
+
+impl Store {
+    fn add_record(&mut self, table: &str, record: Value) {
+        let records = self.tables.entry(table.to_owned()).or_insert_with(Vec::new);
+        match records.iter_mut().find(|existing| existing["id"] == record["id"]) {
+            Some(existing) => *existing = record.clone(),
+            None => records.push(record.clone())
+        }
+
+        // listeners which receivers are dropped are removed
+        self.listeners.retain(|listener| !listener.sender.is_closed());
+        for listener in &self.listeners {
+            if listener.table == table && matches_filter(&record, &listener.filter) {
+                let _ = listener.sender.unbounded_send(record.clone());
+            }
+        }
+    }
+
+    fn select(&self, params: &QueryParams) -> Vec<Value> {
+        let mut records: Vec<&Value> = self.tables
+            .get(&params.table)
+            .map(|records| records
+                .iter()
+                .filter(|record| matches_filter(record, &params.filter))
+                .collect())
+            .unwrap_or_default();
+
+        if let Some(order_by) = &params.order_by {
+            let pointer = format!("/{}", order_by.path().replace('.', "/"));
+            records.sort_by(|a, b| {
+                let ordering = compare(
+                    a.pointer(&pointer).unwrap_or(&Value::Null),
+                    b.pointer(&pointer).unwrap_or(&Value::Null)).unwrap_or(Ordering::Equal);
+                match order_by.direction() {
+                    SortDirection::Ascending => ordering,
+                    SortDirection::Descending => ordering.reverse()
+                }
+            });
+        }
+
+        let selection = Selection::parse(&params.fields);
+        records
+            .into_iter()
+            .take(params.limit.unwrap_or(DEFAULT_LIMIT) as usize)
+            .map(|record| selection.project(record))
+            .collect()
+    }
+
+    fn listen(&mut self, table: &str, filter: &Value) -> mpsc::UnboundedReceiver<Value> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.listeners.push(Listener { table: table.to_owned(), filter: filter.clone(), sender });
+        receiver
+    }
+}
+
+// Transport keeping records in memory. Posted messages are processed by the message
+// processor which by default creates successful finalized transaction for every message.
+// Clones share the same store, so a clone can be used to fill it after the transport
+// is passed to `NodeClient`
+#[derive(Clone, Default)]
+pub struct InMemoryTransport {
+    store: Arc<Mutex<Store>>,
+}
+
+impl InMemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds record to the table replacing existing one with the same id
+    pub fn add_record(&self, table: &str, record: Value) {
+        self.store.lock().unwrap().add_record(table, record);
+    }
+
+    pub fn records(&self, table: &str) -> Vec<Value> {
+        self.store.lock().unwrap().tables.get(table).cloned().unwrap_or_default()
+    }
+
+    // Returns ids and bodies of the messages posted to the transport
+    pub fn sent_messages(&self) -> Vec<(String, Vec<u8>)> {
+        self.store.lock().unwrap().sent_messages.clone()
+    }
+
+    pub fn set_message_processor<F>(&self, processor: F)
+        where F: Fn(&str, &[u8]) -> Vec<(String, Value)> + Send + Sync + 'static
+    {
+        self.store.lock().unwrap().processor = Some(Arc::new(processor));
+    }
+
+    // Makes the transport behave like a server without the `info` query
+    pub fn set_legacy_server(&self, legacy: bool) {
+        self.store.lock().unwrap().legacy_server = legacy;
+    }
+
+    // Makes subscriptions fail like on a server without websocket support
+    pub fn set_subscriptions_disabled(&self, disabled: bool) {
+        self.store.lock().unwrap().subscriptions_disabled = disabled;
+    }
+
+    // Shifts the server clock, e.g. to emulate skewed local clock
+    pub fn set_time_offset(&self, offset: i64) {
+        self.store.lock().unwrap().time_offset = offset;
+    }
+
+    // Stores posted message and creates successful finalized transaction for it
+    pub fn default_message_processor(id: &str, body: &[u8]) -> Vec<(String, Value)> {
+        let transaction = json!({
+            "id": hex::encode(Sha256::digest(id.as_bytes())),
+            "status": json_helper::transaction_status_to_u8(TransactionProcessingStatus::Finalized),
+            "now": chrono::Utc::now().timestamp(),
+            "in_msg": id,
+            "block_id": hex::encode(Sha256::digest(format!("block {}", id).as_bytes())),
+            "out_msgs": [],
+            "aborted": false,
+            "compute": {
+                "compute_type": 1,
+                "exit_code": 0,
+                "success": true
+            }
+        });
+
+        vec![
+            (MESSAGES_TABLE_NAME.to_owned(), json!({
+                "id": id,
+                "msg_type": 1,
+                // finalized
+                "status": 5,
+                "boc": base64::encode(body)
+            })),
+            (TRANSACTIONS_TABLE_NAME.to_owned(), transaction),
+        ]
+    }
+}
+
+impl Transport for InMemoryTransport {
+    fn query<'a>(&'a self, params: &'a QueryParams) -> TransportFuture<'a, Value> {
+        Box::pin(async move {
+            let timeout = params.timeout.unwrap_or(0);
+            let mut receiver = {
+                let mut store = self.store.lock().unwrap();
+                let records = store.select(params);
+                if !records.is_empty() || timeout == 0 {
+                    return Ok(Value::Array(records));
+                }
+                // listener is registered under the same lock so no record can be missed
+                store.listen(&params.table, &params.filter)
+            };
+
+            let _ = tokio::time::timeout(Duration::from_millis(timeout as u64), receiver.next()).await;
+
+            Ok(Value::Array(self.store.lock().unwrap().select(params)))
+        })
+    }
+
+    fn query_batch<'a>(&'a self, params: &'a [QueryParams]) -> TransportFuture<'a, Vec<Result<Value>>> {
+        Box::pin(async move {
+            let mut results = Vec::new();
+            for params in params {
+                results.push(self.query(params).await);
+            }
+            Ok(results)
+        })
+    }
+
+    fn subscribe<'a>(&'a self, table: &'a str, filter: &'a Value, fields: &'a str)
+        -> TransportFuture<'a, RecordStream>
+    {
+        Box::pin(async move {
+            let receiver = {
+                let mut store = self.store.lock().unwrap();
+                if store.subscriptions_disabled {
+                    return Err(SdkError::NetworkError { msg: "Subscriptions are not supported".to_owned() }.into());
+                }
+                store.listen(table, filter)
+            };
+            let selection = Selection::parse(fields);
+            Ok(Box::pin(receiver.map(move |record| Ok(selection.project(&record)))) as RecordStream)
+        })
+    }
+
+    fn send_message<'a>(&'a self, id: &'a [u8], body: &'a [u8]) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            let id = hex::encode(id);
+            let processor = {
+                let mut store = self.store.lock().unwrap();
+                store.sent_messages.push((id.clone(), body.to_vec()));
+                store.processor.clone()
+            };
+
+            // processor is called without lock so it can use the transport itself
+            let records = match processor {
+                Some(processor) => processor(&id, body),
+                None => Self::default_message_processor(&id, body)
+            };
+
+            let mut store = self.store.lock().unwrap();
+            for (table, record) in records {
+                store.add_record(&table, record);
+            }
+            Ok(())
+        })
+    }
+
+    fn server_info<'a>(&'a self) -> TransportFuture<'a, ServerInfo> {
+        Box::pin(async move {
+            let store = self.store.lock().unwrap();
+            let (global_id, zerostate_hash) = network_identity(&Value::Array(store.select(&network_block_query())));
+
+            let mut features: Vec<String> = TABLES.iter().map(|table| table.to_string()).collect();
+            let version = if store.legacy_server {
+                None
+            } else {
+                features.push("info".to_owned());
+                Some(SERVER_VERSION.to_owned())
+            };
+
+            Ok(ServerInfo { version, features, global_id, zerostate_hash })
+        })
+    }
+
+    fn server_time<'a>(&'a self) -> TransportFuture<'a, i64> {
+        Box::pin(async move {
+            Ok(chrono::Utc::now().timestamp_millis() + self.store.lock().unwrap().time_offset)
+        })
+    }
+}
+
+// Compares scalar values. Numbers given as strings (like `"0x10"` or `"16"`) are compared
+// with numbers and with hex strings numerically
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    fn as_number(value: &Value) -> Option<f64> {
+        match value {
+            Value::Number(number) => number.as_f64(),
+            Value::String(string) if string.starts_with("0x") =>
+                u128::from_str_radix(&string[2..], 16).ok().map(|number| number as f64),
+            Value::String(string) => string.parse().ok(),
+            _ => None
+        }
+    }
+
+    match (a, b) {
+        (Value::String(a), Value::String(b)) if !a.starts_with("0x") && !b.starts_with("0x") =>
+            Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (a, b) => as_number(a)?.partial_cmp(&as_number(b)?)
+    }
+}
+
+fn field_matches(value: &Value, condition: &Value) -> bool {
+    let condition = match condition.as_object() {
+        Some(condition) => condition,
+        None => return false
+    };
+
+    condition.iter().all(|(operation, argument)| match operation.as_str() {
+        "eq" => compare(value, argument) == Some(Ordering::Equal),
+        "ne" => compare(value, argument) != Some(Ordering::Equal),
+        "gt" => compare(value, argument) == Some(Ordering::Greater),
+        "lt" => compare(value, argument) == Some(Ordering::Less),
+        "ge" => compare(value, argument).map(|ordering| ordering != Ordering::Less).unwrap_or(false),
+        "le" => compare(value, argument).map(|ordering| ordering != Ordering::Greater).unwrap_or(false),
+        "in" => argument.as_array()
+            .map(|list| list.iter().any(|item| compare(value, item) == Some(Ordering::Equal)))
+            .unwrap_or(false),
+        "notIn" => argument.as_array()
+            .map(|list| list.iter().all(|item| compare(value, item) != Some(Ordering::Equal)))
+            .unwrap_or(true),
+        "any" => value.as_array()
+            .map(|items| items.iter().any(|item| field_matches(item, argument)))
+            .unwrap_or(false),
+        "all" => value.as_array()
+            .map(|items| items.iter().all(|item| field_matches(item, argument)))
+            .unwrap_or(false),
+        // nested structure field
+        field => field_matches(&value[field], argument)
+    })
+}
+
+// Checks record against GraphQL server filter: fields conditions are joined with AND,
+// `OR` contains alternative filter
+pub fn matches_filter(record: &Value, filter: &Value) -> bool {
+    let filter = match filter.as_object() {
+        Some(filter) => filter,
+        None => return true
+    };
+
+    let matches = filter
+        .iter()
+        .filter(|(field, _)| field.as_str() != "OR")
+        .all(|(field, condition)| field_matches(&record[field.as_str()], condition));
+
+    matches || filter.get("OR").map(|or| matches_filter(record, or)).unwrap_or(false)
+}
+
+// Parsed GraphQL selection set like `id compute { exit_code }`
+struct Selection(Vec<(String, Selection)>);
+
+impl Selection {
+    fn parse(fields: &str) -> Self {
+        let fields = fields.replace('{', " { ").replace('}', " } ");
+        Self::parse_tokens(&mut fields.split_whitespace())
+    }
+
+    fn parse_tokens<'a>(tokens: &mut impl Iterator<Item=&'a str>) -> Self {
+        let mut fields: Vec<(String, Selection)> = Vec::new();
+        while let Some(token) = tokens.next() {
+            match token {
+                "}" => break,
+                "{" => {
+                    let nested = Self::parse_tokens(tokens);
+                    if let Some(last) = fields.last_mut() {
+                        last.1 = nested;
+                    }
+                },
+                name => fields.push((name.to_owned(), Selection(Vec::new())))
+            }
+        }
+        Selection(fields)
+    }
+
+    // Leaves only selected fields of the value
+    fn project(&self, value: &Value) -> Value {
+        if self.0.is_empty() {
+            return value.clone();
+        }
+        match value {
+            Value::Array(items) => Value::Array(items.iter().map(|item| self.project(item)).collect()),
+            Value::Object(object) => {
+                let mut result = Map::new();
+                for (name, nested) in &self.0 {
+                    result.insert(
+                        name.clone(),
+                        object.get(name).map(|value| nested.project(value)).unwrap_or(Value::Null));
+                }
+                Value::Object(result)
+            },
+            value => value.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/test_memory_transport.rs"]
+mod tests;
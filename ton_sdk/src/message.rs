@@ -19,7 +19,7 @@ use ton_types::Result;
 #[cfg(feature = "node_interaction")]
 use crate::node_client::NodeClient;
 #[cfg(feature = "node_interaction")]
-use crate::types::MESSAGES_TABLE_NAME;
+use crate::query_builder::{Fields, MessageField, Messages};
 #[cfg(feature = "node_interaction")]
 use crate::error::SdkError;
 
@@ -56,11 +56,9 @@ pub struct Message {
 }
 
 #[cfg(feature = "node_interaction")]
-const MESSAGE_FIELDS: &str = r#"
-    id
-    body
-    msg_type
-"#;
+fn message_fields() -> Fields<Messages> {
+    Fields::new(&[MessageField::Id, MessageField::Body, MessageField::MsgType, MessageField::Status])
+}
 
 // The struct represents sent message and allows to access their properties.
 #[allow(dead_code)]
@@ -69,10 +67,7 @@ impl Message {
     // Asynchronously loads a Message instance or None if message with given id is not exists
     #[cfg(feature = "node_interaction")]
     pub async fn load(client: &NodeClient, id: &MessageId) -> Result<Option<Message>> {
-        let value = client.load_record_fields(
-            MESSAGES_TABLE_NAME,
-            &id.to_string(),
-            MESSAGE_FIELDS).await?;
+        let value = client.load_record_fields_typed(&id.to_string(), &message_fields()).await?;
 
         if value == serde_json::Value::Null {
             Ok(None)
@@ -84,16 +79,35 @@ impl Message {
         }
     }
 
+    // Asynchronously loads several Message instances in one request.
+    // None is returned for messages which are not exist
+    #[cfg(feature = "node_interaction")]
+    pub async fn load_many(client: &NodeClient, ids: &[MessageId]) -> Result<Vec<Option<Message>>> {
+        let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        let values = client.load_records_fields(&ids, &message_fields()).await?;
+
+        let mut messages = Vec::new();
+        for value in values {
+            if value == serde_json::Value::Null {
+                messages.push(None);
+            } else {
+                messages.push(Some(serde_json::from_value(value)
+                    .map_err(|err| SdkError::InvalidData {
+                        msg: format!("error parsing message: {}", err)
+                    })?));
+            }
+        }
+
+        Ok(messages)
+    }
+
     // Asynchronously loads a Message's json representation 
     // or null if message with given id is not exists
     #[cfg(feature = "node_interaction")]
     pub async fn load_json(client: &NodeClient, id: MessageId) -> Result<String> {
-        client.load_record_fields(
-            MESSAGES_TABLE_NAME,
-            &id.to_string(),
-            MESSAGE_FIELDS)
-                .await
-                .map(|val| val.to_string())
+        client.load_record_fields_typed(&id.to_string(), &message_fields())
+            .await
+            .map(|val| val.to_string())
     }
 
     pub fn with_msg(tvm_msg: &TvmMessage) -> Result<Self> {
@@ -0,0 +1,87 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use graphite::types::RequestMetrics;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    // HTTP request to the server. The only operation which has retries
+    Request,
+    Query,
+    // Query waiting for a record to appear, e.g. a transaction of the sent message
+    WaitFor,
+    QueryBatch,
+    SendMessage,
+    // Record delivered by a subscription. Latency is the time since the subscription
+    // was started or the previous record was delivered
+    SubscriptionRecord,
+}
+
+#[derive(Debug, Clone)]
+pub struct OperationMetrics {
+    pub operation: Operation,
+    // Table of a database operation or GraphQL operations of a request (comma separated)
+    pub table: Option<String>,
+    pub latency: Duration,
+    // Size of the sent message or of the received records. Both directions for requests
+    pub bytes: usize,
+    pub retries: u32,
+    pub succeeded: bool,
+}
+
+// Receives measurements of `NodeClient` operations, e.g. to feed service dashboards.
+// Called synchronously, so it should not block
+pub trait MetricsObserver: Send + Sync {
+    fn on_operation(&self, metrics: &OperationMetrics);
+}
+
+impl<F: Fn(&OperationMetrics) + Send + Sync> MetricsObserver for F {
+    fn on_operation(&self, metrics: &OperationMetrics) {
+        self(metrics)
+    }
+}
+
+// Observer shared by the client and its transport, so it can be set after the transport
+// is created
+#[derive(Clone, Default)]
+pub(crate) struct MetricsSlot(Arc<RwLock<Option<Arc<dyn MetricsObserver>>>>);
+
+impl MetricsSlot {
+    pub fn set(&self, observer: Arc<dyn MetricsObserver>) {
+        *self.0.write().unwrap() = Some(observer);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.read().unwrap().is_some()
+    }
+
+    pub fn report(&self, metrics: OperationMetrics) {
+        if let Some(observer) = self.0.read().unwrap().as_ref() {
+            observer.on_operation(&metrics);
+        }
+    }
+
+    pub fn report_request(&self, request: &RequestMetrics) {
+        self.report(OperationMetrics {
+            operation: Operation::Request,
+            table: if request.operations.is_empty() { None } else { Some(request.operations.join(",")) },
+            latency: request.latency,
+            bytes: request.bytes_sent + request.bytes_received,
+            retries: request.retries,
+            succeeded: request.succeeded,
+        });
+    }
+}
@@ -12,15 +12,23 @@
 * limitations under the License.
 */
 
-use crate::{NodeClientConfig, TimeoutsConfig};
+use crate::{Clock, NodeClientConfig, RetryConfig, ServerInfo, SystemClock, TimeoutsConfig};
 use crate::error::SdkError;
-use graphite::client::GqlClient;
-use graphite::types::{VariableRequest};
-use futures::{TryFutureExt, Stream, StreamExt};
+use crate::metrics::{MetricsObserver, MetricsSlot, Operation, OperationMetrics};
+use crate::query_builder::{Fields, Filter, Table, TableField};
+use crate::records_cache::RecordsCache;
+use crate::transport::{QueryParams, Transport};
+use graphite::client::{GqlClient, RetryPolicy};
+use graphite::endpoints::{Endpoint, EndpointSet};
+use graphite::types::RequestMetrics;
+use futures::{Future, Stream, StreamExt};
+use futures::lock::Mutex;
 use serde_json::Value;
 use reqwest::{ClientBuilder, RedirectPolicy, StatusCode};
 use reqwest::header::LOCATION;
-use ton_types::{error, Result};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use ton_types::Result;
 
 #[derive(Serialize, Deserialize)]
 pub enum SortDirection {
@@ -36,15 +44,58 @@ pub struct OrderBy {
     direction: SortDirection
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct MutationRequest {
-    pub id: String,
-    pub body: String
+impl OrderBy {
+    pub fn new<F: TableField>(field: F, direction: SortDirection) -> Self {
+        Self {
+            path: field.path().to_owned(),
+            direction
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn direction(&self) -> &SortDirection {
+        &self.direction
+    }
+}
+
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+
+// Failed clock synchronization is not repeated for every message during this interval
+const CLOCK_SYNC_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+// Single query of a batch
+pub struct QueryRequest {
+    pub table: String,
+    pub filter: String,
+    pub fields: String,
+    pub order_by: Option<OrderBy>,
+    pub limit: Option<u32>
 }
 
 pub struct NodeClient {
-    client: Option<GqlClient>,
-    timeouts: TimeoutsConfig
+    transport: Option<Box<dyn Transport>>,
+    timeouts: TimeoutsConfig,
+    expected_zerostate_hash: Option<String>,
+    // loaded on the first request since server identity doesn't change
+    server_info: Mutex<Option<ServerInfo>>,
+    clock: Arc<dyn Clock>,
+    max_clock_offset: Option<u32>,
+    clock_offset: std::sync::Mutex<Option<i64>>,
+    // time of the last failed clock synchronization
+    clock_sync_failed: std::sync::Mutex<Option<Instant>>,
+    records_cache: Option<std::sync::Mutex<RecordsCache>>,
+    metrics: MetricsSlot,
+    wait_by_subscription: bool,
+}
+
+// Client time is the time of its clock corrected by the server clock offset
+impl Clock for NodeClient {
+    fn now_ms(&self) -> i64 {
+        self.clock.now_ms() + self.clock_offset.lock().unwrap().unwrap_or(0)
+    }
 }
 
 impl NodeClient {
@@ -97,78 +148,323 @@ impl NodeClient {
         (queries_url, subscriptions_url)
     }
     
-    // Globally initializes client with server address
+    // Resolves endpoint addresses following redirects. Endpoints which can not be checked
+    // are kept with original address and marked as unhealthy, error is returned only
+    // if all of them are unreachable
+    fn resolve_endpoints(addresses: Vec<String>) -> Result<EndpointSet> {
+        let mut endpoints = Vec::new();
+        let mut unreachable = Vec::new();
+        let mut first_error = None;
+        for address in addresses {
+            let (queries_server, subscriptions_server) = Self::expand_address(address);
+            match Self::check_redirect(&queries_server) {
+                Ok(Some(redirected)) => {
+                    let subscriptions_server = redirected
+                        .replace("https://", "wss://")
+                        .replace("http://", "ws://");
+                    endpoints.push(Endpoint::new(&redirected, &subscriptions_server));
+                },
+                Ok(None) => endpoints.push(Endpoint::new(&queries_server, &subscriptions_server)),
+                Err(err) => {
+                    unreachable.push(endpoints.len());
+                    endpoints.push(Endpoint::new(&queries_server, &subscriptions_server));
+                    first_error = first_error.or(Some(err));
+                }
+            }
+        }
+
+        if unreachable.len() == endpoints.len() {
+            if let Some(err) = first_error {
+                return Err(err);
+            }
+        }
+
+        let endpoints = EndpointSet::new(endpoints)?;
+        for index in unreachable {
+            endpoints.mark_unhealthy(index);
+        }
+
+        Ok(endpoints)
+    }
+
+    fn retry_policy(config: RetryConfig) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: config.max_attempts,
+            delay: Duration::from_millis(config.delay as u64),
+            delay_grow_factor: config.delay_grow_factor,
+            max_delay: Duration::from_millis(config.max_delay as u64),
+            retryable_statuses: config.retryable_statuses,
+        }
+    }
+
+    // Globally initializes client with server addresses
     pub fn new(config: NodeClientConfig) -> Result<NodeClient> {
-        let client = if let Some(base_url) = config.base_url {
-            let (mut queries_server, mut subscriptions_server) = Self::expand_address(base_url);
-            if let Some(redirected) = Self::check_redirect(&queries_server)? {
-                queries_server = redirected.clone();
-                subscriptions_server = redirected
-                    .replace("https://", "wss://")
-                    .replace("http://", "ws://");
+        let mut addresses: Vec<String> = config.base_url.into_iter().collect();
+        for address in config.endpoints.unwrap_or_default() {
+            if !addresses.contains(&address) {
+                addresses.push(address);
             }
-            Some(GqlClient::new(&queries_server, &subscriptions_server)?)
+        }
+
+        let metrics = MetricsSlot::default();
+        let transport = if !addresses.is_empty() {
+            let requests_metrics = metrics.clone();
+            let client = GqlClient::new(
+                Self::resolve_endpoints(addresses)?,
+                config.access_key.as_ref().map(|key| key.as_str()))?
+                .with_retry_policy(Self::retry_policy(config.retry.unwrap_or_default()))
+                .with_request_observer(Arc::new(move |request: &RequestMetrics| requests_metrics.report_request(request)));
+            Some(Box::new(client) as Box<dyn Transport>)
         } else {
             None
         };
 
         Ok(NodeClient {
-            client,
-            timeouts: config.timeouts.unwrap_or_default()
+            transport,
+            timeouts: config.timeouts.unwrap_or_default(),
+            expected_zerostate_hash: config.expected_zerostate_hash,
+            server_info: Mutex::new(None),
+            clock: Arc::new(SystemClock),
+            max_clock_offset: config.max_clock_offset,
+            clock_offset: std::sync::Mutex::new(None),
+            clock_sync_failed: std::sync::Mutex::new(None),
+            records_cache: config.records_cache_size
+                .map(|size| std::sync::Mutex::new(RecordsCache::new(size))),
+            metrics,
+            wait_by_subscription: config.wait_by_subscription.unwrap_or(false),
         })
     }
 
+    // Creates client working over provided transport, e.g. in-memory one for testing
+    pub fn with_transport(transport: Box<dyn Transport>, timeouts: TimeoutsConfig) -> NodeClient {
+        NodeClient {
+            transport: Some(transport),
+            timeouts,
+            expected_zerostate_hash: None,
+            server_info: Mutex::new(None),
+            clock: Arc::new(SystemClock),
+            max_clock_offset: None,
+            clock_offset: std::sync::Mutex::new(None),
+            clock_sync_failed: std::sync::Mutex::new(None),
+            records_cache: None,
+            metrics: MetricsSlot::default(),
+            wait_by_subscription: false,
+        }
+    }
+
+    // Replaces the system clock, e.g. with `ManualClock` in tests
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> NodeClient {
+        self.clock = clock;
+        self
+    }
+
+    // Makes `sync_clock` fail if the clock offset exceeds `max_offset` ms
+    pub fn with_max_clock_offset(mut self, max_offset: u32) -> NodeClient {
+        self.max_clock_offset = Some(max_offset);
+        self
+    }
+
+    // Enables caching of up to `size` records with final status loaded by id
+    pub fn with_records_cache(mut self, size: usize) -> NodeClient {
+        self.records_cache = Some(std::sync::Mutex::new(RecordsCache::new(size)));
+        self
+    }
+
+    // Sets observer receiving measurements of client operations and HTTP requests
+    pub fn with_metrics(self, observer: Arc<dyn MetricsObserver>) -> NodeClient {
+        self.metrics.set(observer);
+        self
+    }
+
+    // Makes `listen_for_typed` wait for records through subscription
+    pub fn with_wait_by_subscription(mut self, enabled: bool) -> NodeClient {
+        self.wait_by_subscription = enabled;
+        self
+    }
+
+    // Makes the client send messages only to the network with given zerostate hash
+    pub fn with_expected_network(mut self, zerostate_hash: &str) -> NodeClient {
+        self.expected_zerostate_hash = Some(zerostate_hash.to_owned());
+        self
+    }
+
+    fn transport(&self) -> Result<&dyn Transport> {
+        self.transport
+            .as_ref()
+            .map(|transport| transport.as_ref())
+            .ok_or(SdkError::SdkNotInitialized.into())
+    }
+
     pub fn timeouts(&self) -> &TimeoutsConfig {
         &self.timeouts
     }
     
     // Returns Stream with updates database fileds by provided filter
-    pub fn subscribe(&self, table: &str, filter: &str, fields: &str)
+    pub async fn subscribe(&self, table: &str, filter: &str, fields: &str)
         -> Result<impl Stream<Item=Result<Value>> + Send> {
-    
-        let request = Self::generate_subscription(table, filter, fields)?;
-    
-        let closure_table = table.to_owned();
-
-        let client = self.client.as_ref().ok_or(SdkError::SdkNotInitialized)?;
-        let stream = client.subscribe(request)?
-            .map(move |result| {
-                    match result {
-                        Err(err) => Err(error!(err).into()),
-                        Ok(value) => {
-                            // try to extract the record value from the answer
-                            let record_value = &value["payload"]["data"][&closure_table];
-                            
-                            if record_value.is_null() {
-                                Err(error!(SdkError::InvalidData {
-                                    msg: format!("Invalid subscription answer: {}", value)
-                                }).into())
-                            } else {
-                                Ok(record_value.clone())
-                            }
-                        }
-                    }
-                }
-            );
+        self.subscribe_value(table, serde_json::from_str(filter)?, fields).await
+    }
 
-        Ok(stream)
+    // Returns Stream with updates of typed table records matching the filter
+    pub async fn subscribe_typed<T: Table>(&self, filter: &Filter<T>, fields: &Fields<T>)
+        -> Result<impl Stream<Item=Result<Value>> + Send> {
+        self.subscribe_value(T::NAME, filter.to_value(), &fields.to_string()).await
+    }
+
+    async fn subscribe_value(&self, table: &str, filter: Value, fields: &str)
+        -> Result<impl Stream<Item=Result<Value>> + Send> {
+        let stream = self.transport()?.subscribe(table, &filter, fields).await?;
+
+        let metrics = self.metrics.clone();
+        let table = table.to_owned();
+        let mut last_delivery = Instant::now();
+        Ok(stream.map(move |record| {
+            if metrics.is_set() {
+                metrics.report(OperationMetrics {
+                    operation: Operation::SubscriptionRecord,
+                    table: Some(table.clone()),
+                    latency: last_delivery.elapsed(),
+                    bytes: record.as_ref().map(Self::value_size).unwrap_or(0),
+                    retries: 0,
+                    succeeded: record.is_ok(),
+                });
+            }
+            last_delivery = Instant::now();
+            record
+        }))
     }
     
-    // Returns Stream with required database record fields
+    // Returns required database record fields. Finalized records are taken from the cache
+    // if it is enabled, `id` is always loaded
     pub async fn load_record_fields(&self, table: &str, record_id: &str, fields: &str)
         -> Result<Value> {
-        let value = self.query(
+        let fields = &with_id_field(fields);
+        if let Some(record) = self.cached_record(table, record_id, fields) {
+            return Ok(record);
+        }
+
+        let value = self.query_value(
             table,
-            &format!("{{ \"id\": {{\"eq\": \"{record_id}\" }} }}", record_id=record_id),
+            json!({ "id": { "eq": record_id } }),
             fields,
             None,
             None,
             None).await?;
         
+        self.cache_record(table, record_id, fields, &value[0]);
         Ok(value[0].clone())
     }
+
+    // Returns required fields of typed table record
+    pub async fn load_record_fields_typed<T: Table>(&self, record_id: &str, fields: &Fields<T>)
+        -> Result<Value> {
+        self.load_record_fields(T::NAME, record_id, &fields.to_string()).await
+    }
+
+    // Returns required fields of several records loaded in one request. Records are returned
+    // in order of `record_ids`, `Value::Null` is returned for records which are not exist
+    pub async fn load_records_fields<T: Table>(&self, record_ids: &[String], fields: &Fields<T>)
+        -> Result<Vec<Value>> {
+        let fields = with_id_field(&fields.to_string());
+        let cached: Vec<Option<Value>> = record_ids
+            .iter()
+            .map(|id| self.cached_record(T::NAME, id, &fields))
+            .collect();
+        let missing: Vec<&String> = record_ids
+            .iter()
+            .zip(cached.iter())
+            .filter(|(_, record)| record.is_none())
+            .map(|(id, _)| id)
+            .collect();
+
+        let records = if missing.is_empty() {
+            Vec::new()
+        } else {
+            self.query_value(
+                T::NAME,
+                json!({ "id": { "in": missing } }),
+                &fields,
+                None,
+                Some(missing.len() as u32),
+                None).await?
+                .as_array().cloned().unwrap_or_default()
+        };
+
+        Ok(record_ids
+            .iter()
+            .zip(cached.into_iter())
+            .map(|(id, cached)| cached.unwrap_or_else(|| {
+                let record = records
+                    .iter()
+                    .find(|record| record["id"].as_str() == Some(id.as_str()))
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                self.cache_record(T::NAME, id, &fields, &record);
+                record
+            }))
+            .collect())
+    }
+
+    fn cached_record(&self, table: &str, id: &str, fields: &str) -> Option<Value> {
+        self.records_cache
+            .as_ref()
+            .and_then(|cache| cache.lock().unwrap().get(table, id, fields))
+    }
+
+    fn cache_record(&self, table: &str, id: &str, fields: &str, record: &Value) {
+        if let Some(cache) = &self.records_cache {
+            cache.lock().unwrap().insert(table, id, fields, record);
+        }
+    }
+
+    // Executes several queries in one request. Results are returned in the same order as queries
+    pub async fn query_batch(&self, queries: Vec<QueryRequest>) -> Result<Vec<Result<Value>>> {
+        let mut params = Vec::new();
+        for query in queries {
+            params.push(QueryParams {
+                filter: serde_json::from_str(&query.filter)?,
+                table: query.table,
+                fields: query.fields,
+                order_by: query.order_by,
+                limit: query.limit,
+                timeout: None
+            });
+        }
+
+        let size = |results: &Vec<Result<Value>>| results
+            .iter()
+            .map(|result| result.as_ref().map(Self::value_size).unwrap_or(0))
+            .sum();
+        self.measure(Operation::QueryBatch, None, size, self.transport()?.query_batch(&params)).await
+    }
+
+    // Runs the operation reporting its measurements to the metrics observer
+    async fn measure<T, F, S>(&self, operation: Operation, table: Option<&str>, size: S, future: F) -> Result<T>
+        where F: Future<Output=Result<T>>, S: Fn(&T) -> usize
+    {
+        if !self.metrics.is_set() {
+            return future.await;
+        }
+
+        let start = Instant::now();
+        let result = future.await;
+        self.metrics.report(OperationMetrics {
+            operation,
+            table: table.map(|table| table.to_owned()),
+            latency: start.elapsed(),
+            bytes: result.as_ref().map(size).unwrap_or(0),
+            retries: 0,
+            succeeded: result.is_ok(),
+        });
+        result
+    }
+
+    fn value_size(value: &Value) -> usize {
+        value.to_string().len()
+    }
     
-    // Returns Stream with GraphQL query answer 
+    // Returns Stream with GraphQL query answer. Records cache is not used here since
+    // query results depend on the filter, only records loaded by id are cached
     pub async fn query(
         &self,
         table: &str,
@@ -178,25 +474,244 @@ impl NodeClient {
         limit: Option<u32>,
         timeout: Option<u32>
     ) -> Result<Value> {
-        let query = Self::generate_query_var(table, filter, fields, order_by, limit, timeout)?;
+        self.query_value(table, serde_json::from_str(filter)?, fields, order_by, limit, timeout).await
+    }
 
-        let client = self.client.as_ref().ok_or(SdkError::SdkNotInitialized)?;
-        let result = client.query_vars(query).await?;
-        
-        // try to extract the record value from the answer
-        let records_array = &result["data"][&table];
-        if records_array.is_null() {
-            Err(SdkError::InvalidData { msg: format!("Invalid query answer: {}", result) }.into())
-        } else {
-            Ok(records_array.clone())
+    // Returns typed table records matching the filter
+    pub async fn query_typed<T: Table>(
+        &self,
+        filter: &Filter<T>,
+        fields: &Fields<T>,
+        order_by: Option<(T::Field, SortDirection)>,
+        limit: Option<u32>,
+        timeout: Option<u32>
+    ) -> Result<Value> {
+        self.query_value(
+            T::NAME,
+            filter.to_value(),
+            &fields.to_string(),
+            order_by.map(|(field, direction)| OrderBy::new(field, direction)),
+            limit,
+            timeout).await
+    }
+
+    async fn query_value(
+        &self,
+        table: &str,
+        filter: Value,
+        fields: &str,
+        order_by: Option<OrderBy>,
+        limit: Option<u32>,
+        timeout: Option<u32>
+    ) -> Result<Value> {
+        let params = QueryParams {
+            table: table.to_owned(),
+            filter,
+            fields: fields.to_owned(),
+            order_by,
+            limit,
+            timeout
+        };
+
+        // query with timeout waits for records to appear
+        let operation = if timeout.is_some() { Operation::WaitFor } else { Operation::Query };
+        self.measure(operation, Some(table), Self::value_size, self.transport()?.query(&params)).await
+    }
+
+    // Returns Stream with all records matching the filter. Records are requested page by page
+    // ordered by `key` field which must be unique among the records (e.g. `id`)
+    pub fn query_all<'a>(&'a self, table: &str, filter: &str, fields: &str, key: &str, page_size: u32)
+        -> Result<impl Stream<Item=Result<Value>> + Send + 'a>
+    {
+        Ok(self.query_all_value(
+            table.to_owned(), serde_json::from_str(filter)?, fields.to_owned(), key.to_owned(), page_size))
+    }
+
+    // Returns Stream with all typed table records matching the filter, see `query_all`
+    pub fn query_all_typed<'a, T: Table>(&'a self, filter: &Filter<T>, fields: &Fields<T>, key: T::Field, page_size: u32)
+        -> impl Stream<Item=Result<Value>> + Send + 'a
+    {
+        self.query_all_value(
+            T::NAME.to_owned(), filter.to_value(), fields.to_string(), key.path().to_owned(), page_size)
+    }
+
+    fn query_all_value<'a>(
+        &'a self,
+        table: String,
+        filter: Value,
+        fields: String,
+        key: String,
+        page_size: u32
+    ) -> impl Stream<Item=Result<Value>> + Send + 'a {
+        // key is always requested to know where the next page starts
+        let fields = format!("{} {}", Self::key_selection(&key), fields);
+
+        // state is the key of the last received record, `None` when all pages are loaded
+        futures::stream::unfold(Some(None), move |last_key: Option<Option<Value>>| {
+            let (table, fields, key) = (table.clone(), fields.clone(), key.clone());
+            let mut filter = filter.clone();
+            async move {
+                let last_key = last_key?;
+                // empty pages would never end the pagination
+                if page_size == 0 {
+                    return Some((vec![Err(SdkError::InvalidArg {
+                        msg: "Page size must be greater than zero".to_owned()
+                    }.into())], None));
+                }
+                if let Some(last_key) = last_key {
+                    if let Err(err) = Self::add_key_condition(&mut filter, &key, last_key) {
+                        return Some((vec![Err(err)], None));
+                    }
+                }
+
+                let order_by = OrderBy { path: key.clone(), direction: SortDirection::Ascending };
+                let page = self.query_value(
+                    &table, filter, &fields, Some(order_by), Some(page_size), None).await;
+
+                match page.map(|page| page.as_array().cloned().unwrap_or_default()) {
+                    Err(err) => Some((vec![Err(err)], None)),
+                    Ok(records) => {
+                        let next = if records.len() < page_size as usize {
+                            None
+                        } else {
+                            let pointer = format!("/{}", key.replace('.', "/"));
+                            match records.last().and_then(|record| record.pointer(&pointer)) {
+                                Some(last_key) => Some(Some(last_key.clone())),
+                                None => return Some((vec![Err(SdkError::InvalidData {
+                                    msg: format!("Record has no `{}` field to continue pagination", key)
+                                }.into())], None))
+                            }
+                        };
+                        Some((records.into_iter().map(Ok).collect(), next))
+                    }
+                }
+            }
+        })
+        .map(futures::stream::iter)
+        .flatten()
+    }
+
+    // Builds selection of possibly nested key field: `a.b` -> `a { b }`
+    fn key_selection(key: &str) -> String {
+        let mut segments = key.rsplit('.');
+        let mut selection = segments.next().unwrap_or_default().to_owned();
+        for segment in segments {
+            selection = format!("{} {{ {} }}", segment, selection);
         }
+        selection
     }
-    
+
+    // Restricts filter to records with key greater than the last received one.
+    // Condition is added to every `OR` alternative so each of them is paginated.
+    // `gt` condition of the caller is kept if it is stricter than the last key
+    fn add_key_condition(filter: &mut Value, key: &str, last_key: Value) -> Result<()> {
+        if !filter.is_object() {
+            *filter = json!({});
+        }
+        if let Some(or) = filter.get_mut("OR") {
+            Self::add_key_condition(or, key, last_key.clone())?;
+        }
+
+        let mut target = filter;
+        for segment in key.split('.') {
+            if !target[segment].is_object() {
+                target[segment] = json!({});
+            }
+            target = &mut target[segment];
+        }
+        let condition = match target.get("gt") {
+            None => last_key,
+            Some(gt) => match Self::compare_keys(gt, &last_key) {
+                Some(std::cmp::Ordering::Greater) => gt.clone(),
+                Some(_) => last_key,
+                None => bail!(SdkError::InvalidArg {
+                    msg: format!("Filter condition `{}: {{ gt: {} }}` can't be combined with the page start {}",
+                        key, gt, last_key)
+                })
+            }
+        };
+        target["gt"] = condition;
+
+        Ok(())
+    }
+
+    // Compares key values like the server does: numbers and `0x` prefixed hex strings
+    // numerically, other strings lexicographically. `None` if values are not comparable
+    fn compare_keys(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+        fn hex(string: &str) -> Option<String> {
+            if string.starts_with("0x") {
+                Some(string[2..].trim_start_matches('0').to_lowercase())
+            } else {
+                None
+            }
+        }
+
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+            (Value::String(a), Value::String(b)) => match (hex(a), hex(b)) {
+                (Some(a), Some(b)) => Some(a.len().cmp(&b.len()).then_with(|| a.cmp(&b))),
+                (None, None) => Some(a.cmp(b)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
     // Executes GraphQL query, waits for result and returns recieved value
     pub async fn wait_for(&self, table: &str, filter: &str, fields: &str, timeout: Option<u32>)
         -> Result<Value>
     {
-        let value = self.query(
+        self.wait_for_value(table, serde_json::from_str(filter)?, fields, timeout).await
+    }
+
+    // Waits for typed table record matching the filter and returns it
+    pub async fn wait_for_typed<T: Table>(&self, filter: &Filter<T>, fields: &Fields<T>, timeout: Option<u32>)
+        -> Result<Value>
+    {
+        self.wait_for_value(T::NAME, filter.to_value(), &fields.to_string(), timeout).await
+    }
+
+    // Waits for typed table record like `wait_for_typed`, but through subscription if the
+    // client waits by subscription. Falls back to polling if the subscription can't be made
+    // or fails before the record is received
+    pub async fn listen_for_typed<T: Table>(&self, filter: &Filter<T>, fields: &Fields<T>, timeout: Option<u32>)
+        -> Result<Value>
+    {
+        let timeout = timeout.unwrap_or(self.timeouts.wait_for_timeout);
+        if !self.wait_by_subscription {
+            return self.wait_for_typed(filter, fields, Some(timeout)).await;
+        }
+
+        let start = Instant::now();
+        if let Ok(stream) = self.subscribe_typed(filter, fields).await {
+            // subscription delivers only records changed after it is made, so the record
+            // could be already stored
+            let stored = self.query_value(
+                T::NAME, filter.to_value(), &fields.to_string(), None, Some(1), None).await;
+            match stored {
+                Ok(stored) if !stored[0].is_null() => return Ok(stored[0].clone()),
+                Ok(_) => {
+                    futures::pin_mut!(stream);
+                    let timeout = Duration::from_millis(timeout as u64);
+                    match tokio::time::timeout(timeout, stream.next()).await {
+                        Ok(Some(Ok(record))) => return Ok(record),
+                        Err(_) => return Err(SdkError::WaitForTimeout.into()),
+                        Ok(_) => {}
+                    }
+                },
+                // polling is tried if the stored records can't be checked as well
+                Err(_) => {}
+            }
+        }
+
+        let elapsed = start.elapsed().as_millis() as u32;
+        self.wait_for_typed(filter, fields, Some(timeout.saturating_sub(elapsed).max(1))).await
+    }
+
+    async fn wait_for_value(&self, table: &str, filter: Value, fields: &str, timeout: Option<u32>)
+        -> Result<Value>
+    {
+        let value = self.query_value(
             table,
             filter,
             fields,
@@ -211,82 +726,114 @@ impl NodeClient {
         }
     }
     
-    fn generate_query_var(
-        table: &str,
-        filter: &str,
-        fields: &str,
-        order_by: Option<OrderBy>,
-        limit: Option<u32>,
-        timeout: Option<u32>
-    ) -> Result<VariableRequest> {
-        let mut scheme_type = (&table[0 .. table.len() - 1]).to_owned() + "Filter";
-        scheme_type[..1].make_ascii_uppercase();
-    
-        let mut query = format!(
-            r#"query {table}
-            ($filter: {scheme_type}, $orderBy: [QueryOrderBy], $limit: Int, $timeout: Float)
-            {{ 
-                {table}(filter: $filter, orderBy: $orderBy, limit: $limit, timeout: $timeout)
-                {{ {fields} }}
-            }}"#,
-            table=table,
-            scheme_type=scheme_type,
-            fields=fields
-        );
-        query = query.split_whitespace().collect::<Vec<&str>>().join(" ");
-    
-        let variables = json!({
-            "filter" : serde_json::from_str::<Value>(filter)?,
-            "orderBy": order_by,
-            "limit": limit,
-            "timeout": timeout
-        });
-    
-        let variables = variables.to_string().split_whitespace().collect::<Vec<&str>>().join(" ");
-    
-        Ok(VariableRequest::new(query, Some(variables)))
+    // Returns server version, schema features and network identity
+    pub async fn server_info(&self) -> Result<ServerInfo> {
+        let mut cached = self.server_info.lock().await;
+        if let Some(info) = cached.as_ref() {
+            return Ok(info.clone());
+        }
+
+        let info = self.transport()?.server_info().await?;
+        *cached = Some(info.clone());
+        Ok(info)
     }
-    
-    fn generate_subscription(table: &str, filter: &str, fields: &str) -> Result<VariableRequest> {
-        let mut scheme_type = (&table[0 .. table.len() - 1]).to_owned() + "Filter";
-        scheme_type[..1].make_ascii_uppercase();
-    
-        let query = format!("subscription {table}($filter: {type}) {{ {table}(filter: $filter) {{ {fields} }} }}",
-            type=scheme_type,
-            table=table,
-            fields=fields);
-        let query = query.split_whitespace().collect::<Vec<&str>>().join(" ");
-    
-        let variables = json!({
-            "filter" : serde_json::from_str::<Value>(filter)?
-        });
-        let variables = variables.to_string().split_whitespace().collect::<Vec<&str>>().join(" ");
-    
-        Ok(VariableRequest::new(query, Some(variables)))
+
+    // Estimates the offset of the server clock relative to the local one. The offset is
+    // applied to the time the client reports as a `Clock`. Returns the offset in ms
+    pub async fn sync_clock(&self) -> Result<i64> {
+        let start = self.clock.now_ms();
+        let server_time = self.transport()?.server_time().await?;
+        let end = self.clock.now_ms();
+
+        // server time is considered to be taken in the middle of the request
+        let offset = server_time - start - (end - start) / 2;
+        *self.clock_offset.lock().unwrap() = Some(offset);
+
+        self.check_clock_offset(offset)
     }
-    
-    fn generate_post_mutation(requests: &[MutationRequest]) -> Result<VariableRequest> {
-        let query = "mutation postRequests($requests:[Request]){postRequests(requests:$requests)}".to_owned();
-        let variables = json!({
-            "requests": serde_json::to_value(requests)?
-        }).to_string();
-    
-        Ok(VariableRequest::new(query, Some(variables)))
+
+    fn check_clock_offset(&self, offset: i64) -> Result<i64> {
+        match self.max_clock_offset {
+            Some(max) if offset.abs() > max as i64 => bail!(SdkError::ClockOutOfSync { offset, max }),
+            _ => Ok(offset)
+        }
     }
-    
-    // Sends message to node
-    pub async fn send_message(&self, key: &[u8], value: &[u8]) -> Result<()> {
-        let request = MutationRequest {
-            id: base64::encode(key),
-            body: base64::encode(value)
+
+    // Synchronizes clock before the first message is created. Messages are still sent
+    // with the local time if the server time can't be got, the synchronization is retried
+    // after `CLOCK_SYNC_RETRY_INTERVAL`
+    pub async fn sync_clock_once(&self) -> Result<()> {
+        let synced = *self.clock_offset.lock().unwrap();
+        let result = match synced {
+            Some(offset) => self.check_clock_offset(offset),
+            None => {
+                let failed = *self.clock_sync_failed.lock().unwrap();
+                if let Some(failed) = failed {
+                    if failed.elapsed() < CLOCK_SYNC_RETRY_INTERVAL {
+                        return Ok(());
+                    }
+                }
+
+                let result = self.sync_clock().await;
+                // offset is set if the server time is got even if it is out of sync
+                if result.is_err() && self.clock_offset.lock().unwrap().is_none() {
+                    *self.clock_sync_failed.lock().unwrap() = Some(Instant::now());
+                }
+                result
+            }
         };
 
-        let client = self.client.as_ref().ok_or(SdkError::SdkNotInitialized)?;
-        client.query_vars(Self::generate_post_mutation(&[request])?)
-            .map_err(|_| SdkError::NetworkError {
-                    msg: "Post message error: server did not responded".to_owned()
-                }.into())
-            .map_ok(|_| ())
-            .await
+        match result {
+            Err(err) => match err.downcast_ref::<SdkError>() {
+                Some(SdkError::ClockOutOfSync { .. }) => Err(err),
+                _ => Ok(())
+            },
+            Ok(_) => Ok(())
+        }
+    }
+
+    // Checks that the server belongs to the expected network if one is configured.
+    // Servers which don't report their zerostate are rejected too
+    async fn check_network(&self) -> Result<()> {
+        if let Some(expected) = &self.expected_zerostate_hash {
+            let actual = self.server_info().await?.zerostate_hash;
+            if actual.as_ref() != Some(expected) {
+                bail!(SdkError::WrongNetwork {
+                    expected: expected.clone(),
+                    actual: actual.unwrap_or_else(|| "unknown".to_owned())
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Sends message to node
+    pub async fn send_message(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.check_network().await?;
+        self.measure(Operation::SendMessage, None, |_| value.len(), self.transport()?.send_message(key, value)).await
     }    
 }
+
+// Adds `id` to the selection if it is not selected on the top level, so records are loaded
+// with the same fields and share cache entries whether they are loaded one by one or in bulk
+fn with_id_field(fields: &str) -> String {
+    let mut depth = 0;
+    let selected = fields
+        .replace('{', " { ")
+        .replace('}', " } ")
+        .split_whitespace()
+        .any(|token| {
+            match token {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            depth == 0 && token == "id"
+        });
+
+    if selected {
+        fields.trim().to_owned()
+    } else {
+        format!("id {}", fields.trim())
+    }
+}
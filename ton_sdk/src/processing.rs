@@ -0,0 +1,317 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use crate::error::SdkError;
+use crate::json_helper;
+use crate::query_builder::{BlockField, Blocks, Fields, Filter, TransactionField, Transactions};
+use crate::transaction::transaction_fields_ordinary;
+use crate::{Clock, Contract, MessageId, NodeClient, Transaction};
+use futures::channel::mpsc;
+use futures::FutureExt;
+use serde_json::Value;
+use std::sync::Arc;
+use ton_block::TransactionProcessingStatus;
+use ton_types::Result;
+
+const MESSAGE_EXPIRED_CODE: i32 = 57;
+const REPLAY_PROTECTION_CODE: i32 = 52;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessingEvent {
+    MessageSent { message_id: String, expire: Option<u32>, try_index: u8 },
+    // Transaction of the message is produced by the shard but may be not finalized yet
+    ShardBlockFound { message_id: String, block_id: String },
+    TransactionFound { message_id: String, transaction_id: String },
+    // Message can't be processed anymore: it wasn't delivered before expiration time
+    // or was rejected by the contract as expired
+    Expired { message_id: String, try_index: u8 },
+    // Message expired and is going to be rebuilt and sent again
+    Retrying { try_index: u8 },
+}
+
+// Receives events of message processing, e.g. to display the processing status.
+// Called synchronously, so it should not block
+pub trait ProcessingObserver: Send + Sync {
+    fn on_event(&self, event: &ProcessingEvent);
+}
+
+impl<F: Fn(&ProcessingEvent) + Send + Sync> ProcessingObserver for F {
+    fn on_event(&self, event: &ProcessingEvent) {
+        self(event)
+    }
+}
+
+// Observer delivering events to the returned stream
+pub fn processing_events() -> (Arc<dyn ProcessingObserver>, mpsc::UnboundedReceiver<ProcessingEvent>) {
+    let (sender, receiver) = mpsc::unbounded();
+    let observer = move |event: &ProcessingEvent| {
+        // events are dropped if nobody listens anymore
+        let _ = sender.unbounded_send(event.clone());
+    };
+    (Arc::new(observer), receiver)
+}
+
+pub(crate) fn notify(events: Option<&dyn ProcessingObserver>, event: ProcessingEvent) {
+    if let Some(events) = events {
+        events.on_event(&event);
+    }
+}
+
+// Serializable state of the message processing. Allows to resume waiting for the message
+// transaction later, e.g. after the application restart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessingState {
+    pub message_id: String,
+    #[serde(
+        rename = "messageBodyBase64",
+        serialize_with = "json_helper::serialize_bytes_to_base64",
+        deserialize_with = "json_helper::deserialize_bytes_from_base64")]
+    pub message: Vec<u8>,
+    pub expire: Option<u32>,
+    pub try_index: u8,
+    pub sent: bool,
+    // Time the waiting for the transaction is started, i.e. the message is sent
+    pub start_time: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Created,
+    Sent,
+    ShardBlockFound,
+    Finished,
+}
+
+// State machine of the message processing. Every step is performed by `next_event` call
+// and produces one event: message is sent, transaction is found in the shard block, then
+// the transaction is finalized or the message is expired
+pub struct MessageProcessing {
+    message_id: MessageId,
+    message: Vec<u8>,
+    expire: Option<u32>,
+    try_index: u8,
+    start_time: Option<u32>,
+    // message was sent before the processing is resumed, so it could expire meanwhile
+    resumed: bool,
+    stage: Stage,
+    // transaction found in the shard block
+    found: Option<Value>,
+    transaction: Option<Transaction>,
+}
+
+impl MessageProcessing {
+    pub fn new(message_id: MessageId, message: Vec<u8>, expire: Option<u32>, try_index: u8) -> Self {
+        Self {
+            message_id,
+            message,
+            expire,
+            try_index,
+            start_time: None,
+            resumed: false,
+            stage: Stage::Created,
+            found: None,
+            transaction: None,
+        }
+    }
+
+    // Processing of the message which is already sent
+    pub fn sent(message_id: MessageId, expire: Option<u32>, try_index: u8) -> Self {
+        Self { stage: Stage::Sent, ..Self::new(message_id, Vec::new(), expire, try_index) }
+    }
+
+    // Processing continuing from the saved state. The message is sent again only if it
+    // wasn't sent before the state is saved
+    pub fn resume(state: ProcessingState) -> Self {
+        let stage = if state.sent { Stage::Sent } else { Stage::Created };
+        Self {
+            start_time: state.start_time,
+            resumed: state.sent,
+            stage,
+            ..Self::new(state.message_id.into(), state.message, state.expire, state.try_index)
+        }
+    }
+
+    pub fn state(&self) -> ProcessingState {
+        ProcessingState {
+            message_id: self.message_id.to_string(),
+            message: self.message.clone(),
+            expire: self.expire,
+            try_index: self.try_index,
+            sent: self.stage != Stage::Created,
+            start_time: self.start_time,
+        }
+    }
+
+    pub fn message_id(&self) -> &MessageId {
+        &self.message_id
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.stage == Stage::Finished
+    }
+
+    // Transaction of the message after `TransactionFound` event
+    pub fn take_transaction(&mut self) -> Option<Transaction> {
+        self.transaction.take()
+    }
+
+    // Performs steps until the message is processed
+    pub async fn run(&mut self, client: &NodeClient, events: Option<&dyn ProcessingObserver>) -> Result<Transaction> {
+        loop {
+            let event = self.next_event(client).await?;
+            notify(events, event.clone());
+            match event {
+                ProcessingEvent::TransactionFound { .. } => {
+                    return self.take_transaction().ok_or_else(|| SdkError::InternalError {
+                        msg: "Found transaction is already taken".to_owned()
+                    }.into());
+                },
+                ProcessingEvent::Expired { .. } => return Err(SdkError::MessageExpired.into()),
+                _ => {}
+            }
+        }
+    }
+
+    pub async fn next_event(&mut self, client: &NodeClient) -> Result<ProcessingEvent> {
+        let message_id = self.message_id.to_string();
+        match self.stage {
+            Stage::Created => {
+                client.send_message(&self.message_id.to_bytes()?, &self.message).await?;
+                self.start_time = Some(client.now()?);
+                self.stage = Stage::Sent;
+                Ok(ProcessingEvent::MessageSent { message_id, expire: self.expire, try_index: self.try_index })
+            },
+            Stage::Sent => {
+                if self.start_time.is_none() {
+                    self.start_time = Some(client.now()?);
+                }
+                let filter = Filter::<Transactions>::new().eq(TransactionField::InMsg, message_id.clone());
+                let fields = transaction_fields_ordinary().with(TransactionField::BlockId);
+                match self.wait_transaction(client, filter, fields, !self.resumed).await? {
+                    Some(transaction) => {
+                        let block_id = transaction["block_id"].as_str().unwrap_or_default().to_owned();
+                        self.found = Some(transaction);
+                        self.stage = Stage::ShardBlockFound;
+                        Ok(ProcessingEvent::ShardBlockFound { message_id, block_id })
+                    },
+                    None => Ok(self.expired())
+                }
+            },
+            Stage::ShardBlockFound => {
+                let finalized = json_helper::transaction_status_to_u8(TransactionProcessingStatus::Finalized);
+                let transaction = match self.found.take() {
+                    Some(transaction) if transaction["status"] == Value::from(finalized) => Some(transaction),
+                    _ => {
+                        let filter = Filter::<Transactions>::new()
+                            .eq(TransactionField::InMsg, message_id.clone())
+                            .eq(TransactionField::Status, finalized);
+                        self.wait_transaction(client, filter, transaction_fields_ordinary(), false).await?
+                    }
+                };
+                let transaction = match transaction {
+                    Some(transaction) => serde_json::from_value::<Transaction>(transaction)?,
+                    None => return Ok(self.expired())
+                };
+
+                if transaction.compute.exit_code == Some(MESSAGE_EXPIRED_CODE) ||
+                    transaction.compute.exit_code == Some(REPLAY_PROTECTION_CODE)
+                {
+                    return Ok(self.expired());
+                }
+
+                let transaction_id = transaction.id.to_string();
+                self.transaction = Some(transaction);
+                self.stage = Stage::Finished;
+                Ok(ProcessingEvent::TransactionFound { message_id, transaction_id })
+            },
+            Stage::Finished => Err(SdkError::InvalidOperation {
+                msg: format!("Processing of message {} is finished", message_id)
+            }.into())
+        }
+    }
+
+    fn expired(&mut self) -> ProcessingEvent {
+        self.stage = Stage::Finished;
+        ProcessingEvent::Expired { message_id: self.message_id.to_string(), try_index: self.try_index }
+    }
+
+    // Waits for the transaction of the message. Returns `None` if the message is expired
+    async fn wait_transaction(
+        &self,
+        client: &NodeClient,
+        filter: Filter<Transactions>,
+        fields: Fields<Transactions>,
+        check_expired: bool,
+    ) -> Result<Option<Value>> {
+        // timeout is growing from try to try
+        let mut timeout = Contract::calc_timeout(
+            client.timeouts().message_processing_timeout,
+            client.timeouts().message_processing_timeout_grow_factor,
+            self.try_index);
+
+        let now = client.now()?;
+        let expire = match self.expire {
+            Some(expire) => expire,
+            None => {
+                // time spent before the processing is resumed is taken into account
+                let elapsed = now.saturating_sub(self.start_time.unwrap_or(now)).saturating_mul(1000);
+                let timeout = timeout.saturating_sub(elapsed).max(1);
+                return client.listen_for_typed(&filter, &fields, Some(timeout)).await.map(Some);
+            }
+        };
+
+        if expire > now {
+            // timeout is time to `expire` plus additional time for masterblock awaiting
+            timeout = (expire - now) * 1000 + timeout;
+        } else if check_expired {
+            return Err(SdkError::InvalidArg { msg: "Message already expired".to_owned() }.into());
+        }
+
+        let transaction_future = client.listen_for_typed(&filter, &fields, Some(timeout));
+
+        // if message expiration time is set we make another future waiting for the masterchain
+        // block which reference to shadchain blocks generated after message expiration: it
+        // guarantees that message won't be processed by the contract later
+        let block_future = async {
+            let block = client.wait_for_typed(
+                &Filter::<Blocks>::new().ge(BlockField::MasterMinShardGenUtime, expire),
+                &Fields::new(&[BlockField::InMsgDescrTransactionId]),
+                Some(timeout)).await?;
+
+            // if we've recieved masterblock check that transactions from this block
+            // are already put into DB and there is no lag in transaction topic
+            match block["in_msg_descr"][0]["transaction_id"].as_str() {
+                None => Err(SdkError::InvalidData {
+                    msg: "Invalid block recieved: no transaction ID".to_owned()
+                }.into()),
+                Some(string) => {
+                    client.wait_for_typed(
+                        &Filter::<Transactions>::new().eq(TransactionField::Id, string),
+                        &Fields::new(&[TransactionField::Id]),
+                        Some(timeout)).await?;
+
+                    Ok(None)
+                }
+            }
+        };
+
+        // awaiting the first future resolved
+        futures::pin_mut!(transaction_future, block_future);
+        futures::select! {
+            transaction = transaction_future.fuse() => transaction.map(Some),
+            block = block_future.fuse() => block,
+        }
+    }
+}
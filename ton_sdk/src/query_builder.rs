@@ -0,0 +1,320 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use crate::error::SdkError;
+use crate::types::{BLOCKS_TABLE_NAME, CONTRACTS_TABLE_NAME, MESSAGES_TABLE_NAME, TRANSACTIONS_TABLE_NAME};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::marker::PhantomData;
+use ton_types::Result;
+
+// Field of a database table. Nested fields are represented by dot-separated path
+pub trait TableField: Copy {
+    fn path(&self) -> &'static str;
+}
+
+// Database table with its GraphQL filter type and set of fields
+pub trait Table {
+    const NAME: &'static str;
+    const FILTER_TYPE: &'static str;
+    // Fields which contain arrays, conditions on them are matched against any item
+    const ARRAY_FIELDS: &'static [&'static str];
+
+    type Field: TableField;
+}
+
+// Returns GraphQL filter type name for the table
+pub fn filter_type(table: &str) -> Result<String> {
+    match table {
+        Accounts::NAME => Ok(Accounts::FILTER_TYPE.to_owned()),
+        Messages::NAME => Ok(Messages::FILTER_TYPE.to_owned()),
+        Blocks::NAME => Ok(Blocks::FILTER_TYPE.to_owned()),
+        Transactions::NAME => Ok(Transactions::FILTER_TYPE.to_owned()),
+        "blocks_signatures" => Ok("BlockSignaturesFilter".to_owned()),
+        "" => Err(SdkError::InvalidArg { msg: "Table name is empty".to_owned() }.into()),
+        _ => {
+            // other tables are named as plural of the record type
+            let mut filter_type = (&table[0 .. table.len() - 1]).to_owned() + "Filter";
+            filter_type[..1].make_ascii_uppercase();
+            Ok(filter_type)
+        }
+    }
+}
+
+macro_rules! table {
+    ($table:ident, $name:expr, $filter_type:expr, [$($array:expr),*], $field:ident {
+        $($variant:ident => $path:expr),* $(,)?
+    }) => {
+        pub struct $table;
+
+        impl Table for $table {
+            const NAME: &'static str = $name;
+            const FILTER_TYPE: &'static str = $filter_type;
+            const ARRAY_FIELDS: &'static [&'static str] = &[$($array),*];
+
+            type Field = $field;
+        }
+
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub enum $field {
+            $($variant),*
+        }
+
+        impl TableField for $field {
+            fn path(&self) -> &'static str {
+                match self {
+                    $($field::$variant => $path),*
+                }
+            }
+        }
+    }
+}
+
+table!(Accounts, CONTRACTS_TABLE_NAME, "AccountFilter", ["balance_other"], AccountField {
+    Id => "id",
+    AccType => "acc_type",
+    LastPaid => "last_paid",
+    DuePayment => "due_payment",
+    LastTransLt => "last_trans_lt",
+    Balance => "balance",
+    BalanceOtherCurrency => "balance_other.currency",
+    BalanceOtherValue => "balance_other.value",
+    Code => "code",
+    Data => "data",
+    Library => "library",
+    Boc => "boc",
+});
+
+table!(Messages, MESSAGES_TABLE_NAME, "MessageFilter", [], MessageField {
+    Id => "id",
+    MsgType => "msg_type",
+    Status => "status",
+    Body => "body",
+    Src => "src",
+    Dst => "dst",
+    Value => "value",
+    CreatedAt => "created_at",
+    CreatedLt => "created_lt",
+    Bounce => "bounce",
+    Bounced => "bounced",
+    Boc => "boc",
+});
+
+table!(Blocks, BLOCKS_TABLE_NAME, "BlockFilter", ["in_msg_descr"], BlockField {
+    Id => "id",
+    Status => "status",
+    GlobalId => "global_id",
+    Workchain => "workchain_id",
+    Shard => "shard",
+    SeqNo => "seq_no",
+    GenUtime => "gen_utime",
+    PrevRefRootHash => "prev_ref.root_hash",
+    PrevRefSeqNo => "prev_ref.seq_no",
+    MasterMinShardGenUtime => "master.min_shard_gen_utime",
+    InMsgDescrMsgId => "in_msg_descr.msg_id",
+    InMsgDescrTransactionId => "in_msg_descr.transaction_id",
+    Boc => "boc",
+});
+
+table!(Transactions, TRANSACTIONS_TABLE_NAME, "TransactionFilter", ["out_msgs"], TransactionField {
+    Id => "id",
+    TrType => "tr_type",
+    Status => "status",
+    BlockId => "block_id",
+    AccountAddr => "account_addr",
+    Lt => "lt",
+    Now => "now",
+    InMsg => "in_msg",
+    OutMsgs => "out_msgs",
+    Aborted => "aborted",
+    ComputeType => "compute.compute_type",
+    ComputeSkippedReason => "compute.skipped_reason",
+    ComputeExitCode => "compute.exit_code",
+    ComputeSuccess => "compute.success",
+    ComputeGasFees => "compute.gas_fees",
+    StorageStatusChange => "storage.status_change",
+    ActionSuccess => "action.success",
+    ActionValid => "action.valid",
+    ActionNoFunds => "action.no_funds",
+    ActionResultCode => "action.result_code",
+    Boc => "boc",
+});
+
+// Filter of table records. Conditions are joined with AND, `or` joins alternative filter.
+pub struct Filter<T: Table> {
+    conditions: Vec<(T::Field, &'static str, Value)>,
+    or: Option<Box<Filter<T>>>,
+}
+
+impl<T: Table> Default for Filter<T> {
+    fn default() -> Self {
+        Self { conditions: Vec::new(), or: None }
+    }
+}
+
+impl<T: Table> Filter<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn condition(mut self, field: T::Field, operation: &'static str, value: Value) -> Self {
+        self.conditions.push((field, operation, value));
+        self
+    }
+
+    pub fn eq<V: Into<Value>>(self, field: T::Field, value: V) -> Self {
+        self.condition(field, "eq", value.into())
+    }
+
+    pub fn ne<V: Into<Value>>(self, field: T::Field, value: V) -> Self {
+        self.condition(field, "ne", value.into())
+    }
+
+    pub fn gt<V: Into<Value>>(self, field: T::Field, value: V) -> Self {
+        self.condition(field, "gt", value.into())
+    }
+
+    pub fn ge<V: Into<Value>>(self, field: T::Field, value: V) -> Self {
+        self.condition(field, "ge", value.into())
+    }
+
+    pub fn lt<V: Into<Value>>(self, field: T::Field, value: V) -> Self {
+        self.condition(field, "lt", value.into())
+    }
+
+    pub fn le<V: Into<Value>>(self, field: T::Field, value: V) -> Self {
+        self.condition(field, "le", value.into())
+    }
+
+    pub fn in_list<V: Into<Value>>(self, field: T::Field, values: Vec<V>) -> Self {
+        let values = values.into_iter().map(|value| value.into()).collect();
+        self.condition(field, "in", Value::Array(values))
+    }
+
+    pub fn not_in<V: Into<Value>>(self, field: T::Field, values: Vec<V>) -> Self {
+        let values = values.into_iter().map(|value| value.into()).collect();
+        self.condition(field, "notIn", Value::Array(values))
+    }
+
+    // Records matching either this filter or `other` one
+    pub fn or(mut self, other: Filter<T>) -> Self {
+        self.or = Some(Box::new(match self.or.take() {
+            Some(or) => or.or(other),
+            None => other
+        }));
+        self
+    }
+
+    // Builds condition for nested field path like `{ "a": { "any": { "b": { "eq": 1 } } } }`
+    fn condition_value(field: &T::Field, operation: &str, value: &Value) -> Value {
+        let segments: Vec<&str> = field.path().split('.').collect();
+        let mut result = json!({ operation: value });
+        for i in (0..segments.len()).rev() {
+            if T::ARRAY_FIELDS.contains(&segments[..=i].join(".").as_str()) {
+                result = json!({ "any": result });
+            }
+            result = json!({ segments[i]: result });
+        }
+        result
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut result = Value::Object(Map::new());
+        for (field, operation, value) in &self.conditions {
+            merge(&mut result, Self::condition_value(field, operation, value));
+        }
+        if let Some(or) = &self.or {
+            result["OR"] = or.to_value();
+        }
+        result
+    }
+}
+
+fn merge(target: &mut Value, source: Value) {
+    match (target, source) {
+        (Value::Object(target), Value::Object(source)) => {
+            for (key, value) in source {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => { target.insert(key, value); }
+                }
+            }
+        },
+        (target, source) => *target = source
+    }
+}
+
+// Set of fields to be returned by query
+pub struct Fields<T: Table> {
+    fields: Vec<T::Field>,
+    _table: PhantomData<T>,
+}
+
+impl<T: Table> Fields<T> {
+    pub fn new(fields: &[T::Field]) -> Self {
+        Self { fields: fields.to_vec(), _table: PhantomData }
+    }
+
+    pub fn with(mut self, field: T::Field) -> Self {
+        self.fields.push(field);
+        self
+    }
+}
+
+// Tree of field names keeping the order fields were added in
+struct FieldsTree(Vec<(&'static str, FieldsTree)>);
+
+impl FieldsTree {
+    fn insert(&mut self, path: &[&'static str]) {
+        if let Some((first, rest)) = path.split_first() {
+            let index = match self.0.iter().position(|(name, _)| name == first) {
+                Some(index) => index,
+                None => {
+                    self.0.push((first, FieldsTree(Vec::new())));
+                    self.0.len() - 1
+                }
+            };
+            (self.0[index].1).insert(rest);
+        }
+    }
+}
+
+impl fmt::Display for FieldsTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (name, children)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", name)?;
+            if !children.0.is_empty() {
+                write!(f, " {{ {} }}", children)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: Table> fmt::Display for Fields<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut tree = FieldsTree(Vec::new());
+        for field in &self.fields {
+            tree.insert(&field.path().split('.').collect::<Vec<&str>>());
+        }
+        write!(f, "{}", tree)
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/test_query_builder.rs"]
+mod tests;
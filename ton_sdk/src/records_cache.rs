@@ -0,0 +1,79 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use crate::types::{BLOCKS_TABLE_NAME, MESSAGES_TABLE_NAME, TRANSACTIONS_TABLE_NAME};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+
+// Final statuses (finalized and refused) of records of every table
+const FINAL_TRANSACTION_STATUSES: [u64; 2] = [3, 4];
+const FINAL_MESSAGE_STATUSES: [u64; 2] = [5, 6];
+const FINAL_BLOCK_STATUSES: [u64; 2] = [2, 3];
+
+// Returns true if the record can't be changed anymore
+pub(crate) fn is_final(table: &str, record: &Value) -> bool {
+    let statuses: &[u64] = match table {
+        TRANSACTIONS_TABLE_NAME => &FINAL_TRANSACTION_STATUSES,
+        MESSAGES_TABLE_NAME => &FINAL_MESSAGE_STATUSES,
+        BLOCKS_TABLE_NAME => &FINAL_BLOCK_STATUSES,
+        _ => return false
+    };
+
+    record["status"].as_u64().map(|status| statuses.contains(&status)).unwrap_or(false)
+}
+
+// Bounded cache of records which are not changed anymore, keyed by table and id. Only
+// records loaded with `status` field can be cached. A record is stored with the fields
+// it was loaded with and is returned only for the same fields. When the cache is full
+// the oldest record is evicted
+pub(crate) struct RecordsCache {
+    capacity: usize,
+    records: HashMap<(String, String), (String, Value)>,
+    order: VecDeque<(String, String)>,
+}
+
+impl RecordsCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&self, table: &str, id: &str, fields: &str) -> Option<Value> {
+        match self.records.get(&(table.to_owned(), id.to_owned())) {
+            Some((cached_fields, record)) if cached_fields == fields => Some(record.clone()),
+            _ => None
+        }
+    }
+
+    // Stores the record if it has final status
+    pub fn insert(&mut self, table: &str, id: &str, fields: &str, record: &Value) {
+        if self.capacity == 0 || !is_final(table, record) {
+            return;
+        }
+
+        let key = (table.to_owned(), id.to_owned());
+        if self.records.insert(key.clone(), (fields.to_owned(), record.clone())).is_none() {
+            self.order.push_back(key);
+        }
+
+        while self.order.len() > self.capacity {
+            if let Some(key) = self.order.pop_front() {
+                self.records.remove(&key);
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use super::*;
+use crate::{NodeClient, TimeoutsConfig};
+use crate::memory_transport::InMemoryTransport;
+
+async fn wait_for_message(client: &NodeClient) -> Result<serde_json::Value> {
+    client.wait_for("messages", &json!({ "id": { "eq": "1" } }).to_string(), "id", Some(5000)).await
+}
+
+fn assert_sdk_error(err: failure::Error, expected: SdkError) {
+    match err.downcast_ref::<SdkError>() {
+        Some(err) if err.to_string() == expected.to_string() => {},
+        _ => panic!("{} expected, got {}", expected, err)
+    }
+}
+
+#[tokio::main]
+#[test]
+async fn test_cancel_wait_for() {
+    let transport = InMemoryTransport::new();
+    let client = NodeClient::with_transport(Box::new(transport.clone()), TimeoutsConfig::default());
+    let token = CancellationToken::new();
+
+    let wait = token.run(wait_for_message(&client));
+    let cancel = async {
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+        token.cancel();
+    };
+    let started = std::time::Instant::now();
+    let (result, _) = futures::join!(wait, cancel);
+
+    assert_sdk_error(result.unwrap_err(), SdkError::OperationCancelled);
+    assert!(started.elapsed() < Duration::from_millis(1000));
+    assert!(token.is_cancelled());
+
+    // cancelled token doesn't start new operations
+    let result = token.run(wait_for_message(&client)).await;
+    assert_sdk_error(result.unwrap_err(), SdkError::OperationCancelled);
+
+    // operations of another token are not affected
+    let wait = CancellationToken::new().run(wait_for_message(&client));
+    transport.add_record("messages", json!({ "id": "1" }));
+    assert_eq!(wait.await.unwrap().unwrap(), json!({ "id": "1" }));
+}
+
+#[tokio::main]
+#[test]
+async fn test_deadline() {
+    let transport = InMemoryTransport::new();
+    let client = NodeClient::with_transport(Box::new(transport.clone()), TimeoutsConfig::default());
+
+    let started = std::time::Instant::now();
+    let result = with_deadline(wait_for_message(&client), Some(100)).await;
+    assert_sdk_error(result.unwrap_err(), SdkError::OperationTimeout);
+    assert!(started.elapsed() < Duration::from_millis(1000));
+
+    transport.add_record("messages", json!({ "id": "1" }));
+    let result = with_deadline(wait_for_message(&client), Some(1000)).await;
+    assert_eq!(result.unwrap().unwrap(), json!({ "id": "1" }));
+
+    let result = with_deadline(wait_for_message(&client), None).await;
+    assert_eq!(result.unwrap().unwrap(), json!({ "id": "1" }));
+
+    // operation dropped on the deadline is not kept by the token
+    let token = CancellationToken::new();
+    let result = with_deadline(token.run(futures::future::pending::<()>()), Some(100)).await;
+    assert_sdk_error(result.unwrap_err(), SdkError::OperationTimeout);
+    assert_eq!(token.running_count(), 0);
+    token.run(wait_for_message(&client)).await.unwrap().unwrap();
+    assert_eq!(token.running_count(), 0);
+}
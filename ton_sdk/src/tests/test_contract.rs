@@ -14,7 +14,7 @@
 
 use ton_abi::json_abi::decode_function_response;
 use super::*;
-use crate::{ContractImage, init_json, MessageType};
+use crate::{ContractImage, init_json, InternalMessageParams, MessageType, OtherCurrencyValue};
 use ed25519_dalek::Keypair;
 use rand::rngs::OsRng;
 use sha2::Sha512;
@@ -197,7 +197,7 @@ async fn test_expire() {
 
     let wallet_address = deploy_contract_and_wait(&client, &WALLET_IMAGE, &WALLET_ABI, "{}", &keypair, 0).await;
 
-    let msg = Contract::construct_call_message_json(
+    let msg = Contract::construct_call_message_json_with_clock(
         wallet_address.clone(),
         FunctionCallSet {
             func: "setSubscriptionAccount".to_owned(),
@@ -212,9 +212,10 @@ async fn test_expire() {
         false,
         Some(&keypair),
         None,
-        None).unwrap();
+        None,
+        &client).unwrap();
 
-    let result = Contract::process_message(&client, msg.message, Some(Contract::now().unwrap() + 1), 0).await;
+    let result = Contract::process_message(&client, msg.message, Some(client.now().unwrap() + 1), 0).await;
 
     match result {
         Err(error) => match error.downcast_ref::<SdkError>().unwrap() {
@@ -225,6 +226,94 @@ async fn test_expire() {
     }
 }
 
+#[test]
+fn test_expire_header_clock() {
+    let address = MsgAddressInt::with_standart(None, 0, vec![0x11; 32].into()).unwrap();
+    let clock = crate::ManualClock::new(1_600_000_000_000);
+    let timeouts = TimeoutsConfig::default();
+    let construct = |try_index| Contract::construct_call_message_json_with_clock(
+        address.clone(),
+        FunctionCallSet {
+            func: "setSubscriptionAccount".to_owned(),
+            header: None,
+            input: json!({
+                "addr": address.to_string()
+            }).to_string(),
+            abi: WALLET_ABI.clone(),
+        },
+        false,
+        None,
+        Some(&timeouts),
+        try_index,
+        &clock).unwrap();
+
+    let timeout = timeouts.message_expiration_timeout / 1000;
+    assert_eq!(construct(None).expire, Some(1_600_000_000 + timeout));
+
+    clock.advance(5000);
+    assert_eq!(construct(None).expire, Some(1_600_000_005 + timeout));
+
+    // timeout grows with retries
+    assert!(construct(Some(1)).expire.unwrap() > 1_600_000_005 + timeout);
+}
+
+#[test]
+fn test_internal_message() {
+    let src = MsgAddressInt::with_standart(None, 0, vec![0x11; 32].into()).unwrap();
+    let image = ContractImage::from_state_init(&mut WALLET_IMAGE.as_slice()).unwrap();
+    let dst = image.msg_address(0);
+
+    let msg = Contract::construct_internal_message_json(
+        InternalMessageParams {
+            src: Some(src.clone()),
+            dst: dst.clone(),
+            value: 1_000_000_000,
+            other_currencies: vec![OtherCurrencyValue { currency: 1, value: 100 }],
+            bounce: true,
+            state_init: Some(image.clone().state_init()),
+        },
+        Some(FunctionCallSet {
+            func: "setSubscriptionAccount".to_owned(),
+            header: None,
+            input: json!({
+                "addr": src.to_string()
+            }).to_string(),
+            abi: WALLET_ABI.clone(),
+        })).unwrap();
+
+    // message survives serialization
+    let (bytes, _) = Contract::serialize_message(msg).unwrap();
+    let msg = Contract::deserialize_message(&bytes).unwrap();
+
+    match msg.header() {
+        ton_block::CommonMsgInfo::IntMsgInfo(header) => {
+            assert_eq!(header.src, ton_block::MsgAddressIntOrNone::Some(src));
+            assert_eq!(header.dst, dst);
+            assert_eq!(header.value.grams, 1_000_000_000u128.into());
+            assert!(!header.value.other.is_empty());
+            assert!(header.bounce);
+        },
+        _ => panic!("Internal message expected")
+    }
+    assert_eq!(msg.state_init(), Some(&image.state_init()));
+
+    let call = Contract::decode_unknown_function_call_json(WALLET_ABI.clone(), msg.body().unwrap(), true).unwrap();
+    assert_eq!(call.function_name, "setSubscriptionAccount");
+
+    // message without body and state init
+    let msg = Contract::create_internal_message(
+        InternalMessageParams { dst: dst.clone(), value: 1, ..InternalMessageParams::default() },
+        None).unwrap();
+    assert!(msg.body().is_none() && msg.state_init().is_none());
+    match msg.header() {
+        ton_block::CommonMsgInfo::IntMsgInfo(header) => {
+            assert_eq!(header.src, ton_block::MsgAddressIntOrNone::None);
+            assert!(!header.bounce);
+        },
+        _ => panic!("Internal message expected")
+    }
+}
+
 #[tokio::main]
 #[test]
 async fn test_retries() {
@@ -0,0 +1,391 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use super::*;
+use crate::{
+    Clock, ManualClock, MessageProcessing, NodeClient, NodeClientConfig, OperationMetrics, ProcessingEvent,
+    SdkError, TimeoutsConfig};
+use crate::memory_transport::SERVER_VERSION;
+use crate::node_client::QueryRequest;
+use crate::query_builder::{Fields, MessageField, Messages};
+use graphite::client::GqlClient;
+use graphite::endpoints::{Endpoint, EndpointSet};
+use graphite::types::VariableRequest;
+use crate::tests_common::{
+    call_contract, call_contract_and_wait, contract_call_local, deploy_contract_and_wait,
+    PIGGY_BANK_CONTRACT_ABI, PIGGY_BANK_CONTRACT_IMAGE};
+use chrono::Utc;
+use ed25519_dalek::Keypair;
+use rand::rngs::OsRng;
+use sha2::Sha512;
+use std::sync::Arc;
+
+fn create_client(server: &LocalServer) -> NodeClient {
+    NodeClient::new(NodeClientConfig {
+        base_url: Some(server.url()),
+        endpoints: None,
+        timeouts: None,
+        access_key: None,
+        retry: None,
+        expected_zerostate_hash: None,
+        max_clock_offset: None,
+        records_cache_size: None,
+        wait_by_subscription: None,
+    }).unwrap()
+}
+
+#[test]
+fn test_parse_operation() {
+    let operation = parse_operation(
+        "query transactions ($filter: TransactionFilter, $limit: Int) \
+        { transactions(filter: $filter, limit: $limit) { id compute { exit_code } } }").unwrap();
+    assert_eq!(operation.kind, "query");
+    assert_eq!(operation.field, "transactions");
+    assert_eq!(operation.selection, "id compute { exit_code }");
+
+    let operation = parse_operation(
+        "mutation postRequests($requests:[Request]){postRequests(requests:$requests)}").unwrap();
+    assert_eq!(operation.kind, "mutation");
+    assert_eq!(operation.field, "postRequests");
+    assert_eq!(operation.selection, "");
+
+    assert!(parse_operation("query").is_err());
+}
+
+#[tokio::main]
+#[test]
+async fn test_queries() {
+    let server = LocalServer::with_transport(InMemoryTransport::new()).unwrap();
+    for i in 0..3 {
+        server.transport().add_record("messages", json!({ "id": format!("{}", i), "msg_type": i }));
+    }
+    let client = create_client(&server);
+
+    let result = client.query("messages", &json!({ "msg_type": { "ge": 1 } }).to_string(), "id", None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(result, json!([{ "id": "1" }, { "id": "2" }]));
+
+    let request = |table: &str, filter: Value, fields: &str| QueryRequest {
+        table: table.to_owned(),
+        filter: filter.to_string(),
+        fields: fields.to_owned(),
+        order_by: None,
+        limit: None,
+    };
+
+    // failed query is reported in its place
+    let results = client.query_batch(vec![
+        request("messages", json!({ "id": { "eq": "2" } }), "msg_type"),
+        request("unknowns", json!({}), "id"),
+        request("blocks", json!({}), "id"),
+        request("messages", json!({ "id": { "eq": "0" } }), "msg_type"),
+    ]).await.unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0].as_ref().unwrap(), &json!([{ "msg_type": 2 }]));
+    match results[1].as_ref().unwrap_err().downcast_ref::<SdkError>() {
+        Some(SdkError::Graphql { err }) => assert_eq!(err.errors()[0].code(), Some("GRAPHQL_VALIDATION_FAILED")),
+        _ => panic!("SdkError::Graphql expected")
+    }
+    assert_eq!(results[2].as_ref().unwrap(), &json!([]));
+    assert_eq!(results[3].as_ref().unwrap(), &json!([{ "msg_type": 0 }]));
+
+    // invalid request fails the whole batch
+    assert!(client.query_batch(vec![
+        request("messages", json!({}), "id"),
+        request("", json!({}), "id"),
+    ]).await.is_err());
+
+    // records are loaded in the order of ids, missing ones are null
+    let ids: Vec<String> = ["2", "5", "0"].iter().map(|id| id.to_string()).collect();
+    let records = client.load_records_fields(&ids, &Fields::<Messages>::new(&[MessageField::MsgType]))
+        .await
+        .unwrap();
+    assert_eq!(records, vec![json!({ "id": "2", "msg_type": 2 }), Value::Null, json!({ "id": "0", "msg_type": 0 })]);
+
+    let wait = client.wait_for("messages", &json!({ "id": { "eq": "3" } }).to_string(), "id", Some(5000));
+    let add = async {
+        tokio::time::delay_for(std::time::Duration::from_millis(100)).await;
+        server.transport().add_record("messages", json!({ "id": "3", "msg_type": 2 }));
+    };
+    let (result, _) = futures::join!(wait, add);
+    assert_eq!(result.unwrap(), json!({ "id": "3" }));
+}
+
+#[tokio::main]
+#[test]
+async fn test_subscribe() {
+    let server = LocalServer::with_transport(InMemoryTransport::new()).unwrap();
+    let client = create_client(&server);
+
+    let stream = client.subscribe("messages", &json!({ "msg_type": { "eq": 1 } }).to_string(), "id")
+        .await
+        .unwrap();
+
+    let add = async {
+        tokio::time::delay_for(std::time::Duration::from_millis(100)).await;
+        server.transport().add_record("messages", json!({ "id": "1", "msg_type": 0 }));
+        server.transport().add_record("messages", json!({ "id": "2", "msg_type": 1 }));
+    };
+    let (records, _) = futures::join!(stream.take(1).map(|record| record.unwrap()).collect::<Vec<_>>(), add);
+
+    assert_eq!(records, vec![json!({ "id": "2" })]);
+}
+
+#[tokio::main]
+#[test]
+async fn test_hostile_filter_values() {
+    let server = LocalServer::with_transport(InMemoryTransport::new()).unwrap();
+    let client = create_client(&server);
+
+    let values = [r#"""#, r#"\"#, r#"\"}"#, "\n\t", r#"", "variables": {}}"#, "  two  spaces  "];
+    for (i, value) in values.iter().enumerate() {
+        server.transport().add_record("messages", json!({ "id": value, "msg_type": i }));
+    }
+
+    for (i, value) in values.iter().enumerate() {
+        let result = client.query("messages", &json!({ "id": { "eq": value } }).to_string(), "id msg_type", None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(result, json!([{ "id": value, "msg_type": i }]));
+    }
+}
+
+#[tokio::main]
+#[test]
+async fn test_graphql_errors() {
+    let server = LocalServer::with_transport(InMemoryTransport::new()).unwrap();
+    let url = format!("{}/graphql", server.url());
+    let client = GqlClient::new(EndpointSet::new(vec![Endpoint::new(&url, &url)]).unwrap(), None).unwrap();
+
+    let unsupported = || VariableRequest::new("query unknown { unknown { id } }".to_owned(), None);
+
+    let err = client.query_vars(unsupported()).await.unwrap_err();
+    assert_eq!(err.errors().len(), 1);
+    assert_eq!(err.errors()[0].code(), Some("GRAPHQL_VALIDATION_FAILED"));
+    assert_eq!(err.message(), err.errors()[0].message);
+
+    // results follow the order of requests, failed one doesn't affect others
+    server.transport().add_record("messages", json!({ "id": "1" }));
+    server.transport().add_record("blocks", json!({ "id": "2" }));
+    let results = client.query_vars_batch(&[
+        VariableRequest::new("query blocks { blocks { id } }".to_owned(), None),
+        unsupported(),
+        VariableRequest::new("query messages { messages { id } }".to_owned(), None),
+    ]).await.unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap(), &json!({ "data": { "blocks": [{ "id": "2" }] } }));
+    assert_eq!(results[1].as_ref().unwrap_err().errors()[0].code(), Some("GRAPHQL_VALIDATION_FAILED"));
+    assert_eq!(results[2].as_ref().unwrap(), &json!({ "data": { "messages": [{ "id": "1" }] } }));
+
+    let err = graphite::types::try_extract_error(&json!({ "errors": [
+        { "message": "first", "path": ["accounts"], "extensions": { "code": "TIMEOUT" } },
+        { "message": "second", "locations": [{ "line": 1, "column": 2 }] },
+    ]})).unwrap();
+    assert_eq!(err.message(), "first; second");
+    assert_eq!(err.errors()[0].code(), Some("TIMEOUT"));
+    assert_eq!(err.errors()[0].path, Some(json!(["accounts"])));
+    assert_eq!(err.errors()[1].code(), None);
+    assert_eq!(err.errors()[1].locations, Some(json!([{ "line": 1, "column": 2 }])));
+}
+
+const ZEROSTATE_HASH: &str = "b0b5ed0a52ab8ee4b2f6d8c5e0c6bc7e5f2ea2fbb6e8c8dc61cb3c6de9d1b0c7";
+
+fn add_network_block(transport: &InMemoryTransport) {
+    transport.add_record("blocks", json!({
+        "id": "1",
+        "workchain_id": -1,
+        "seq_no": 1,
+        "global_id": 42,
+        "prev_ref": { "root_hash": ZEROSTATE_HASH }
+    }));
+}
+
+#[tokio::main]
+#[test]
+async fn test_server_info() {
+    let server = LocalServer::with_transport(InMemoryTransport::new()).unwrap();
+    add_network_block(server.transport());
+
+    let info = create_client(&server).server_info().await.unwrap();
+    assert_eq!(info.version.as_ref().map(|version| version.as_str()), Some(SERVER_VERSION));
+    assert_eq!(info.version_number(), Some(27000));
+    assert!(info.supports("info") && info.supports("messages"));
+    assert_eq!(info.global_id, Some(42));
+    assert_eq!(info.zerostate_hash.as_ref().map(|hash| hash.as_str()), Some(ZEROSTATE_HASH));
+
+    // servers without the `info` query still report their schema and network
+    let server = LocalServer::with_transport(InMemoryTransport::new()).unwrap();
+    server.transport().set_legacy_server(true);
+    add_network_block(server.transport());
+
+    let info = create_client(&server).server_info().await.unwrap();
+    assert_eq!(info.version, None);
+    assert!(!info.supports("info") && info.supports("messages"));
+    assert_eq!(info.global_id, Some(42));
+}
+
+#[tokio::main]
+#[test]
+async fn test_request_metrics() {
+    let server = LocalServer::with_transport(InMemoryTransport::new()).unwrap();
+    let metrics = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let observed = metrics.clone();
+    let client = create_client(&server)
+        .with_metrics(Arc::new(move |metrics: &OperationMetrics| observed.lock().unwrap().push(metrics.clone())));
+
+    client.query("messages", &json!({}).to_string(), "id", None, None, None).await.unwrap();
+
+    // HTTP request is reported before the operation it serves
+    let metrics = metrics.lock().unwrap();
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics[0].operation, crate::metrics::Operation::Request);
+    assert_eq!(metrics[0].table.as_ref().map(|table| table.as_str()), Some("messages"));
+    assert_eq!(metrics[0].retries, 0);
+    assert!(metrics[0].bytes > metrics[1].bytes);
+    assert_eq!(metrics[1].operation, crate::metrics::Operation::Query);
+    assert!(metrics[0].latency <= metrics[1].latency);
+}
+
+#[tokio::main]
+#[test]
+async fn test_expected_network() {
+    let transport = InMemoryTransport::new();
+    add_network_block(&transport);
+
+    let client = NodeClient::with_transport(Box::new(transport.clone()), TimeoutsConfig::default())
+        .with_expected_network("00");
+    match client.send_message(&[1], &[2]).await.unwrap_err().downcast_ref::<SdkError>() {
+        Some(SdkError::WrongNetwork { expected, actual }) => {
+            assert_eq!(expected, "00");
+            assert_eq!(actual, ZEROSTATE_HASH);
+        },
+        _ => panic!("WrongNetwork expected")
+    }
+    assert!(transport.sent_messages().is_empty());
+
+    let client = NodeClient::with_transport(Box::new(transport.clone()), TimeoutsConfig::default())
+        .with_expected_network(ZEROSTATE_HASH);
+    client.send_message(&[1], &[2]).await.unwrap();
+    assert_eq!(transport.sent_messages().len(), 1);
+}
+
+#[tokio::main]
+#[test]
+async fn test_sync_clock() {
+    let transport = InMemoryTransport::new();
+    transport.set_time_offset(3000);
+    let server = LocalServer::with_transport(transport).unwrap();
+
+    let client = create_client(&server);
+    let offset = client.sync_clock().await.unwrap();
+    assert!((offset - 3000).abs() < 1000, "offset {}", offset);
+    assert!((client.now_ms() - Utc::now().timestamp_millis() - 3000).abs() < 1000);
+
+    // the offset corrects the injected clock as well
+    let clock = Arc::new(ManualClock::new(0));
+    let client = create_client(&server).with_clock(clock.clone());
+    client.sync_clock().await.unwrap();
+    assert!((client.now_ms() - Utc::now().timestamp_millis() - 3000).abs() < 1000);
+    clock.advance(60_000);
+    assert!((client.now_ms() - Utc::now().timestamp_millis() - 63000).abs() < 1000);
+
+    let client = NodeClient::with_transport(Box::new(server.transport().clone()), TimeoutsConfig::default())
+        .with_max_clock_offset(1000);
+    match client.sync_clock().await.unwrap_err().downcast_ref::<SdkError>() {
+        Some(SdkError::ClockOutOfSync { max, .. }) => assert_eq!(*max, 1000),
+        _ => panic!("ClockOutOfSync expected")
+    }
+
+    // servers without `info` report the last masterchain block generation time
+    let server = LocalServer::with_transport(InMemoryTransport::new()).unwrap();
+    server.transport().set_legacy_server(true);
+    let now = Utc::now().timestamp();
+    for seq_no in 1..3 {
+        server.transport().add_record("blocks", json!({
+            "id": format!("{}", seq_no),
+            "workchain_id": -1,
+            "seq_no": seq_no,
+            "gen_utime": now - 10 + seq_no * 5
+        }));
+    }
+    let offset = create_client(&server).sync_clock().await.unwrap();
+    assert!(offset.abs() < 1500, "offset {}", offset);
+}
+
+#[tokio::main]
+#[test]
+async fn test_deploy_and_run() {
+    let server = LocalServer::start().unwrap();
+    let client = create_client(&server);
+
+    let mut csprng = OsRng::new().unwrap();
+    let keypair = Keypair::generate::<Sha512, _>(&mut csprng);
+
+    let address = deploy_contract_and_wait(
+        &client,
+        &PIGGY_BANK_CONTRACT_IMAGE,
+        &PIGGY_BANK_CONTRACT_ABI,
+        &json!({ "amount": 123, "goal": "536f6d6520676f616c" }).to_string(),
+        &keypair,
+        0).await;
+
+    let (result, _) = call_contract_and_wait(
+        &client, address.clone(), "getGoal", "{}".to_owned(), &PIGGY_BANK_CONTRACT_ABI, Some(&keypair)).await;
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&result).unwrap(), json!({ "value0": "536f6d6520676f616c" }));
+
+    let result = contract_call_local(&client, address, "getTargetAmount", "{}", &PIGGY_BANK_CONTRACT_ABI, None).await;
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&result).unwrap(), json!({ "value0": "0x7b" }));
+}
+
+#[tokio::main]
+#[test]
+async fn test_expired_message() {
+    let clock = Arc::new(ManualClock::new(Utc::now().timestamp_millis()));
+    let server = LocalServer::start_with_clock(clock.clone()).unwrap();
+    let client = create_client(&server);
+    let dest = "0:fcb91a3a3816d0f7b8c2c76108b8a9bc5a6b7a55bd79f8ab101c52db29232260";
+    let send_grams = || call_contract(
+        &client,
+        MsgAddressInt::from_str(GIVER_ADDRESS).unwrap(),
+        "sendGrams",
+        json!({ "dest": dest, "amount": 1_000_000_000u64 }).to_string(),
+        GIVER_ABI,
+        None);
+
+    send_grams().await;
+    let blocks = server.transport().records("blocks");
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0]["workchain_id"], json!(-1));
+    assert_eq!(blocks[0]["master"]["min_shard_gen_utime"], json!(clock.now().unwrap()));
+
+    // external message to the account without code is dropped by the executor
+    let mut header = ton_block::ExternalInboundMessageHeader::default();
+    header.dst = MsgAddressInt::from_str(dest).unwrap();
+    let (message, message_id) = Contract::serialize_message(TvmMessage::with_ext_in_header(header)).unwrap();
+    let expire = client.now().unwrap() + 10;
+    let mut processing = MessageProcessing::new(message_id, message, Some(expire), 0);
+    match processing.next_event(&client).await.unwrap() {
+        ProcessingEvent::MessageSent { .. } => {},
+        event => panic!("MessageSent expected: {:?}", event)
+    }
+
+    // master block generated after the expiration time proves that the message is expired
+    clock.advance(60_000);
+    send_grams().await;
+    match processing.next_event(&client).await.unwrap() {
+        ProcessingEvent::Expired { try_index, .. } => assert_eq!(try_index, 0),
+        event => panic!("Expired expected: {:?}", event)
+    }
+}
@@ -13,6 +13,7 @@
 */
 
 use crate::tests_common::PIGGY_BANK_CONTRACT_ABI;
+use crate::ManualClock;
 use serde_json::Value;
 use ton_vm::stack::StackItem;
 use ton_vm::stack::integer::IntegerData;
@@ -69,12 +70,17 @@ fn test_executor_call() {
     let contract: crate::Contract = serde_json::from_str(CONTRACT).expect("Error parsing state init");
     let keypair = ed25519_dalek::Keypair::from_bytes(&hex::decode(KEYS).unwrap()).unwrap();
 
-    let result = contract.local_call_json(
+    // storage fee depends on the time passed since `last_paid`
+    let clock = ManualClock::new((1584684866 + 365 * 86400) * 1000);
+    let message = contract.create_call_message_json(
         "transfer".to_owned(),
         None,
         "{\"to\": \"0:e6392da8a96f648098f818501f0211f27c89675e5f196445d211947b48e7c85b\"}".to_owned(),
         PIGGY_BANK_CONTRACT_ABI.to_owned(),
-        Some(&keypair)).expect("Error calling contract");
+        Some(&keypair)).expect("Error creating message");
+    let call = || contract.local_call_with_clock(message.clone(), &clock).expect("Error calling contract");
+
+    let result = call();
     assert!(result.messages.len() == 1);
 
     //println!("{:?}", result.fees);
@@ -84,6 +90,11 @@ fn test_executor_call() {
     assert_eq!(result.fees.out_msgs_fwd_fee, 1000000);
     assert!(result.fees.total_account_fees > 10264122);
     assert!(result.fees.storage_fee > 122);
+
+    assert_eq!(call().fees.storage_fee, result.fees.storage_fee);
+
+    clock.advance(365 * 86400 * 1000);
+    assert!(call().fees.storage_fee > result.fees.storage_fee);
 }
 
 #[test]
@@ -0,0 +1,609 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use super::*;
+use crate::{
+    Clock, Contract, ContractImage, FunctionCallSet, Message, MessageType, NodeClient, OperationMetrics,
+    SdkError, TimeoutsConfig, Transaction};
+use crate::metrics::Operation;
+use crate::processing::{processing_events, MessageProcessing, ProcessingEvent, ProcessingState};
+use crate::query_builder::{Fields, Filter, MessageField, Messages, TransactionField, Transactions};
+use crate::tests_common::{PIGGY_BANK_CONTRACT_ABI, PIGGY_BANK_CONTRACT_IMAGE};
+use ed25519_dalek::Keypair;
+use rand::rngs::OsRng;
+use sha2::Sha512;
+use std::str::FromStr;
+use ton_block::MsgAddressInt;
+
+const ADDRESS: &str = "0:841288ed3b55d9cdafa806807f02a0ae0c169aa5edfe88a789a6482429756a94";
+
+fn create_client(transport: &InMemoryTransport, timeouts: TimeoutsConfig) -> NodeClient {
+    NodeClient::with_transport(Box::new(transport.clone()), timeouts)
+}
+
+fn transfer_call_set() -> FunctionCallSet {
+    FunctionCallSet {
+        func: "transfer".to_owned(),
+        header: None,
+        input: json!({ "to": ADDRESS }).to_string(),
+        abi: PIGGY_BANK_CONTRACT_ABI.to_string(),
+    }
+}
+
+#[test]
+fn test_filter_matching() {
+    let record = json!({
+        "id": "1",
+        "lt": "0x10",
+        "now": 100,
+        "balance": "0x2540be400",
+        "compute": { "exit_code": 0 },
+        "out_msgs": ["a", "b"]
+    });
+
+    assert!(matches_filter(&record, &json!({})));
+    assert!(matches_filter(&record, &json!({ "id": { "eq": "1" }, "now": { "ge": 100, "lt": 101 } })));
+    assert!(matches_filter(&record, &json!({ "lt": { "gt": 15 } })));
+    assert!(matches_filter(&record, &json!({ "balance": { "gt": "0", "lt": "0x300000000" } })));
+    assert!(matches_filter(&record, &json!({ "compute": { "exit_code": { "in": [0, 1] } } })));
+    assert!(matches_filter(&record, &json!({ "out_msgs": { "any": { "eq": "b" } } })));
+    assert!(matches_filter(&record, &json!({ "id": { "eq": "2" }, "OR": { "now": { "eq": 100 } } })));
+
+    assert!(!matches_filter(&record, &json!({ "id": { "ne": "1" } })));
+    assert!(!matches_filter(&record, &json!({ "id": { "notIn": ["1"] } })));
+    assert!(!matches_filter(&record, &json!({ "out_msgs": { "all": { "eq": "b" } } })));
+    assert!(!matches_filter(&record, &json!({ "id": { "eq": "2" }, "OR": { "now": { "eq": 1 } } })));
+}
+
+#[tokio::main]
+#[test]
+async fn test_query_selection_and_order() {
+    let transport = InMemoryTransport::new();
+    for i in 0..5 {
+        transport.add_record("transactions", json!({ "id": format!("{}", i), "now": 10 - i, "aborted": false }));
+    }
+    let client = create_client(&transport, TimeoutsConfig::default());
+
+    let result = client.query(
+        "transactions",
+        &json!({ "now": { "gt": 6 } }).to_string(),
+        "id now",
+        Some(crate::OrderBy::new(TransactionField::Now, crate::SortDirection::Ascending)),
+        Some(2),
+        None).await.unwrap();
+
+    assert_eq!(result, json!([{ "id": "3", "now": 7 }, { "id": "2", "now": 8 }]));
+}
+
+#[tokio::main]
+#[test]
+async fn test_query_all() {
+    let transport = InMemoryTransport::new();
+    for i in 0..12 {
+        transport.add_record("transactions", json!({ "id": format!("{:02}", i), "now": i }));
+    }
+    let pages = Arc::new(std::sync::Mutex::new(0));
+    let counter = pages.clone();
+    let client = create_client(&transport, TimeoutsConfig::default())
+        .with_metrics(Arc::new(move |metrics: &OperationMetrics| if metrics.operation == Operation::Query {
+            *counter.lock().unwrap() += 1;
+        }));
+    let ids = |filter: Value, page_size: u32| {
+        let stream = client.query_all("transactions", &filter.to_string(), "now", "id", page_size).unwrap();
+        stream
+            .map(|record| record.map(|record| record["id"].as_str().unwrap().parse::<u32>().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    // the last page is not full
+    let all: Vec<u32> = ids(json!({}), 5).await.into_iter().map(|id| id.unwrap()).collect();
+    assert_eq!(all, (0..12).collect::<Vec<_>>());
+    assert_eq!(*pages.lock().unwrap(), 3);
+
+    // `gt` of the caller is kept while it is stricter than the page start
+    let result: Vec<u32> = ids(json!({ "id": { "gt": "03" } }), 5).await.into_iter().map(|id| id.unwrap()).collect();
+    assert_eq!(result, (4..12).collect::<Vec<_>>());
+    let result: Vec<u32> = ids(json!({ "id": { "lt": "03" }, "OR": { "id": { "gt": "08" } } }), 2)
+        .await
+        .into_iter()
+        .map(|id| id.unwrap())
+        .collect();
+    assert_eq!(result, vec![0, 1, 2, 9, 10, 11]);
+
+    // conditions which can't be compared are rejected
+    let result = ids(json!({ "id": { "gt": 1, "lt": "03" }, "OR": { "now": { "ge": 0 } } }), 2).await;
+    assert_eq!(result.len(), 3);
+    assert!(result[2].is_err());
+
+    // empty pages are not requested
+    let result = ids(json!({}), 0).await;
+    assert_eq!(result.len(), 1);
+    match result[0].as_ref().unwrap_err().downcast_ref::<SdkError>() {
+        Some(SdkError::InvalidArg { .. }) => {},
+        _ => panic!("SdkError::InvalidArg expected")
+    }
+}
+
+#[tokio::main]
+#[test]
+async fn test_wait_for_record() {
+    let transport = InMemoryTransport::new();
+    let client = create_client(&transport, TimeoutsConfig::default());
+
+    let filter = Filter::<Transactions>::new().eq(TransactionField::InMsg, "123");
+    let fields = Fields::new(&[TransactionField::Id]);
+
+    let wait = client.wait_for_typed(&filter, &fields, Some(5000));
+    let add = async {
+        tokio::time::delay_for(std::time::Duration::from_millis(100)).await;
+        transport.add_record("transactions", json!({ "id": "1", "in_msg": "456" }));
+        transport.add_record("transactions", json!({ "id": "2", "in_msg": "123" }));
+    };
+    let (result, _) = futures::join!(wait, add);
+
+    assert_eq!(result.unwrap(), json!({ "id": "2" }));
+
+    let result = client.wait_for_typed(
+        &Filter::<Transactions>::new().eq(TransactionField::InMsg, "789"), &fields, Some(100)).await;
+    match result.unwrap_err().downcast_ref::<SdkError>() {
+        Some(SdkError::WaitForTimeout) => {},
+        _ => panic!("WaitForTimeout expected")
+    }
+}
+
+#[tokio::main]
+#[test]
+async fn test_listen_for_record() {
+    let metrics = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let observed = metrics.clone();
+    let transport = InMemoryTransport::new();
+    let client = create_client(&transport, TimeoutsConfig::default())
+        .with_wait_by_subscription(true)
+        .with_metrics(Arc::new(move |metrics: &OperationMetrics| observed.lock().unwrap().push(metrics.operation)));
+
+    let filter = Filter::<Transactions>::new().eq(TransactionField::InMsg, "123");
+    let fields = Fields::new(&[TransactionField::Id]);
+
+    let listen = client.listen_for_typed(&filter, &fields, Some(5000));
+    let add = async {
+        tokio::time::delay_for(std::time::Duration::from_millis(100)).await;
+        transport.add_record("transactions", json!({ "id": "1", "in_msg": "456" }));
+        transport.add_record("transactions", json!({ "id": "2", "in_msg": "123" }));
+    };
+    let (result, _) = futures::join!(listen, add);
+
+    assert_eq!(result.unwrap(), json!({ "id": "2" }));
+    assert_eq!(*metrics.lock().unwrap(), vec![Operation::Query, Operation::SubscriptionRecord]);
+
+    // record stored before the subscription is found by the query
+    metrics.lock().unwrap().clear();
+    let result = client.listen_for_typed(&filter, &fields, Some(5000)).await;
+    assert_eq!(result.unwrap(), json!({ "id": "2" }));
+    assert_eq!(*metrics.lock().unwrap(), vec![Operation::Query]);
+
+    let result = client.listen_for_typed(
+        &Filter::<Transactions>::new().eq(TransactionField::InMsg, "789"), &fields, Some(100)).await;
+    match result.unwrap_err().downcast_ref::<SdkError>() {
+        Some(SdkError::WaitForTimeout) => {},
+        _ => panic!("WaitForTimeout expected")
+    }
+
+    // polling is used if subscription fails
+    transport.set_subscriptions_disabled(true);
+    metrics.lock().unwrap().clear();
+    let filter = Filter::<Transactions>::new().eq(TransactionField::InMsg, "321");
+    let listen = client.listen_for_typed(&filter, &fields, Some(5000));
+    let add = async {
+        tokio::time::delay_for(std::time::Duration::from_millis(100)).await;
+        transport.add_record("transactions", json!({ "id": "3", "in_msg": "321" }));
+    };
+    let (result, _) = futures::join!(listen, add);
+
+    assert_eq!(result.unwrap(), json!({ "id": "3" }));
+    assert_eq!(*metrics.lock().unwrap(), vec![Operation::WaitFor]);
+}
+
+#[tokio::main]
+#[test]
+async fn test_subscribe() {
+    let transport = InMemoryTransport::new();
+    let client = create_client(&transport, TimeoutsConfig::default());
+
+    let stream = client.subscribe("messages", &json!({ "msg_type": { "eq": 1 } }).to_string(), "id")
+        .await
+        .unwrap();
+
+    transport.add_record("messages", json!({ "id": "1", "msg_type": 0 }));
+    transport.add_record("messages", json!({ "id": "2", "msg_type": 1 }));
+
+    let records: Vec<serde_json::Value> = stream.take(1).map(|record| record.unwrap()).collect().await;
+    assert_eq!(records, vec![json!({ "id": "2" })]);
+}
+
+#[tokio::main]
+#[test]
+async fn test_call_json() {
+    let transport = InMemoryTransport::new();
+    let client = create_client(&transport, TimeoutsConfig::default());
+
+    let transaction = Contract::call_json(
+        &client,
+        MsgAddressInt::from_str(ADDRESS).unwrap(),
+        transfer_call_set(),
+        None).await.unwrap();
+
+    let sent = transport.sent_messages();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(transaction.in_message_id().unwrap().to_string(), sent[0].0);
+    assert!(!transaction.is_aborted());
+    assert_eq!(transaction.compute.exit_code, Some(0));
+}
+
+#[tokio::main]
+#[test]
+async fn test_call_json_expired() {
+    let transport = InMemoryTransport::new();
+    transport.set_message_processor(|id, body| {
+        let mut records = InMemoryTransport::default_message_processor(id, body);
+        // message expired code
+        records[1].1["compute"]["exit_code"] = json!(57);
+        records
+    });
+    let client = create_client(&transport, TimeoutsConfig {
+        message_retries_count: 2,
+        ..TimeoutsConfig::default()
+    });
+
+    let result = Contract::call_json(
+        &client,
+        MsgAddressInt::from_str(ADDRESS).unwrap(),
+        transfer_call_set(),
+        None).await;
+
+    match result.unwrap_err().downcast_ref::<SdkError>() {
+        Some(SdkError::MessageExpired) => {},
+        _ => panic!("MessageExpired expected")
+    }
+    assert_eq!(transport.sent_messages().len(), 3);
+}
+
+#[tokio::main]
+#[test]
+async fn test_deploy_json() {
+    let transport = InMemoryTransport::new();
+    let client = create_client(&transport, TimeoutsConfig::default());
+
+    let mut csprng = OsRng::new().unwrap();
+    let keypair = Keypair::generate::<Sha512, _>(&mut csprng);
+
+    let image = ContractImage::from_state_init_and_key(
+        &mut PIGGY_BANK_CONTRACT_IMAGE.as_slice(),
+        &keypair.public).unwrap();
+
+    let params = FunctionCallSet {
+        func: "constructor".to_owned(),
+        header: None,
+        input: json!({ "amount": 123, "goal": "536f6d6520676f616c" }).to_string(),
+        abi: PIGGY_BANK_CONTRACT_ABI.to_string(),
+    };
+
+    let transaction = Contract::deploy_json(&client, params, image, Some(&keypair), 0).await.unwrap();
+
+    let sent = transport.sent_messages();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(transaction.in_message_id().unwrap().to_string(), sent[0].0);
+}
+
+fn transaction_record(id: &str, status: u8, aborted: bool) -> serde_json::Value {
+    json!({
+        "id": id,
+        "status": status,
+        "now": 1,
+        "out_msgs": ["1", "2"],
+        "aborted": aborted,
+        "compute": { "compute_type": 1, "exit_code": 0, "success": true }
+    })
+}
+
+async fn out_message_types(client: &NodeClient, transaction: &Transaction) -> Vec<MessageType> {
+    transaction.load_out_messages(client).unwrap()
+        .map(|msg| msg.unwrap().msg_type)
+        .collect()
+        .await
+}
+
+#[tokio::main]
+#[test]
+async fn test_records_cache() {
+    let transport = InMemoryTransport::new();
+    let client = create_client(&transport, TimeoutsConfig::default()).with_records_cache(3);
+
+    // finalized transaction is not reloaded
+    transport.add_record("transactions", transaction_record("1", 3, false));
+    let transaction = Transaction::load(&client, &"1".into()).await.unwrap().unwrap();
+    assert!(!transaction.is_aborted());
+    transport.add_record("transactions", transaction_record("1", 3, true));
+    assert!(!Transaction::load(&client, &"1".into()).await.unwrap().unwrap().is_aborted());
+
+    // preliminary one is
+    transport.add_record("transactions", transaction_record("2", 1, false));
+    Transaction::load(&client, &"2".into()).await.unwrap().unwrap();
+    transport.add_record("transactions", transaction_record("2", 3, true));
+    assert!(Transaction::load(&client, &"2".into()).await.unwrap().unwrap().is_aborted());
+
+    // only missing out messages are requested
+    transport.add_record("messages", json!({ "id": "1", "msg_type": 0, "status": 5, "body": null }));
+    transport.add_record("messages", json!({ "id": "2", "msg_type": 0, "status": 1, "body": null }));
+    assert_eq!(
+        out_message_types(&client, &transaction).await,
+        vec![MessageType::Internal, MessageType::Internal]);
+    transport.add_record("messages", json!({ "id": "1", "msg_type": 2, "status": 5, "body": null }));
+    transport.add_record("messages", json!({ "id": "2", "msg_type": 2, "status": 5, "body": null }));
+    assert_eq!(
+        out_message_types(&client, &transaction).await,
+        vec![MessageType::Internal, MessageType::ExternalOutbound]);
+
+    // the oldest record is evicted when the cache is full
+    assert!(Transaction::load(&client, &"1".into()).await.unwrap().unwrap().is_aborted());
+}
+
+#[tokio::main]
+#[test]
+async fn test_load_many() {
+    let transport = InMemoryTransport::new();
+    transport.add_record("messages", json!({ "id": "1", "msg_type": 0, "status": 5, "body": null }));
+    transport.add_record("messages", json!({ "id": "3", "msg_type": 2, "status": 5, "body": null }));
+    let client = create_client(&transport, TimeoutsConfig::default());
+
+    // records follow the order of requested ids, missing ones are null
+    let ids: Vec<String> = ["3", "2", "1"].iter().map(|id| id.to_string()).collect();
+    let records = client.load_records_fields(&ids, &Fields::<Messages>::new(&[MessageField::MsgType]))
+        .await
+        .unwrap();
+    assert_eq!(records, vec![json!({ "id": "3", "msg_type": 2 }), Value::Null, json!({ "id": "1", "msg_type": 0 })]);
+
+    let messages = Message::load_many(&client, &["3".into(), "2".into(), "1".into()]).await.unwrap();
+    assert_eq!(
+        messages.into_iter().map(|msg| msg.map(|msg| msg.msg_type)).collect::<Vec<_>>(),
+        vec![Some(MessageType::ExternalOutbound), None, Some(MessageType::Internal)]);
+
+    // missing out message is reported in its place
+    transport.add_record("transactions", json!({
+        "id": "1",
+        "status": 3,
+        "now": 1,
+        "out_msgs": ["2", "3"],
+        "aborted": false,
+        "compute": { "compute_type": 1, "exit_code": 0, "success": true }
+    }));
+    let transaction = Transaction::load(&client, &"1".into()).await.unwrap().unwrap();
+    let messages: Vec<Result<Message>> = transaction.load_out_messages(&client).unwrap().collect().await;
+    assert_eq!(messages.len(), 2);
+    match messages[0].as_ref().unwrap_err().downcast_ref::<SdkError>() {
+        Some(SdkError::NoData) => {},
+        _ => panic!("SdkError::NoData expected")
+    }
+    assert_eq!(messages[1].as_ref().unwrap().msg_type, MessageType::ExternalOutbound);
+
+    // records loaded one by one and in bulk share cache entries
+    let client = create_client(&transport, TimeoutsConfig::default()).with_records_cache(3);
+    Message::load(&client, &"1".into()).await.unwrap().unwrap();
+    transport.add_record("messages", json!({ "id": "1", "msg_type": 2, "status": 5, "body": null }));
+    let messages = Message::load_many(&client, &["1".into()]).await.unwrap();
+    assert_eq!(messages[0].as_ref().unwrap().msg_type, MessageType::Internal);
+}
+
+#[tokio::main]
+#[test]
+async fn test_metrics() {
+    let metrics = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let observed = metrics.clone();
+    let transport = InMemoryTransport::new();
+    let client = create_client(&transport, TimeoutsConfig::default())
+        .with_metrics(Arc::new(move |metrics: &OperationMetrics| observed.lock().unwrap().push(metrics.clone())));
+
+    transport.add_record("messages", json!({ "id": "1" }));
+    client.query("messages", &json!({}).to_string(), "id", None, None, None).await.unwrap();
+    client.wait_for("messages", &json!({ "id": { "eq": "2" } }).to_string(), "id", Some(10)).await.unwrap_err();
+    client.send_message(&[1], &[1, 2, 3]).await.unwrap();
+
+    let stream = client.subscribe("messages", &json!({}).to_string(), "id").await.unwrap();
+    transport.add_record("messages", json!({ "id": "3" }));
+    stream.take(1).collect::<Vec<_>>().await;
+
+    let metrics = metrics.lock().unwrap();
+    let operations: Vec<(Operation, Option<&str>)> = metrics
+        .iter()
+        .map(|metrics| (metrics.operation, metrics.table.as_ref().map(|table| table.as_str())))
+        .collect();
+    assert_eq!(operations, vec![
+        (Operation::Query, Some("messages")),
+        (Operation::WaitFor, Some("messages")),
+        (Operation::SendMessage, None),
+        (Operation::SubscriptionRecord, Some("messages")),
+    ]);
+    assert_eq!(metrics[0].bytes, json!([{ "id": "1" }]).to_string().len());
+    assert!(metrics[0].succeeded);
+    assert!(metrics[1].latency >= Duration::from_millis(10));
+    assert_eq!(metrics[2].bytes, 3);
+    assert_eq!(metrics[3].bytes, json!({ "id": "3" }).to_string().len());
+}
+
+#[tokio::main]
+#[test]
+async fn test_processing_events() {
+    let transport = InMemoryTransport::new();
+    let client = create_client(&transport, TimeoutsConfig::default());
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let observed = events.clone();
+
+    let transaction = Contract::call_json_with_events(
+        &client,
+        MsgAddressInt::from_str(ADDRESS).unwrap(),
+        transfer_call_set(),
+        None,
+        Some(&move |event: &ProcessingEvent| observed.lock().unwrap().push(event.clone()))).await.unwrap();
+
+    let message_id = transport.sent_messages()[0].0.clone();
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 3);
+    match &events[0] {
+        ProcessingEvent::MessageSent { message_id: sent, try_index: 0, .. } => assert_eq!(sent, &message_id),
+        event => panic!("MessageSent expected, got {:?}", event)
+    }
+    assert_eq!(events[1], ProcessingEvent::ShardBlockFound {
+        message_id: message_id.clone(),
+        block_id: hex::encode(Sha256::digest(format!("block {}", message_id).as_bytes())),
+    });
+    assert_eq!(events[2], ProcessingEvent::TransactionFound {
+        message_id,
+        transaction_id: transaction.id().to_string(),
+    });
+}
+
+#[tokio::main]
+#[test]
+async fn test_processing_steps() {
+    let transport = InMemoryTransport::new();
+    // transaction is produced by the shard but not finalized yet
+    transport.set_message_processor(|id, body| {
+        let mut records = InMemoryTransport::default_message_processor(id, body);
+        records[1].1["status"] = json!(1);
+        records
+    });
+    let client = create_client(&transport, TimeoutsConfig::default());
+
+    let mut processing = MessageProcessing::new("01".into(), vec![1, 2, 3], None, 0);
+    assert_eq!(
+        processing.next_event(&client).await.unwrap(),
+        ProcessingEvent::MessageSent { message_id: "01".to_owned(), expire: None, try_index: 0 });
+    match processing.next_event(&client).await.unwrap() {
+        ProcessingEvent::ShardBlockFound { .. } => {},
+        event => panic!("ShardBlockFound expected, got {:?}", event)
+    }
+
+    let mut records = InMemoryTransport::default_message_processor("01", &[1, 2, 3]);
+    let (table, record) = records.remove(1);
+    transport.add_record(&table, record);
+
+    match processing.next_event(&client).await.unwrap() {
+        ProcessingEvent::TransactionFound { .. } => {},
+        event => panic!("TransactionFound expected, got {:?}", event)
+    }
+    assert!(processing.is_finished());
+    assert_eq!(processing.take_transaction().unwrap().in_message_id().unwrap().to_string(), "01");
+    assert!(processing.next_event(&client).await.is_err());
+}
+
+#[tokio::main]
+#[test]
+async fn test_processing_events_retries() {
+    let transport = InMemoryTransport::new();
+    transport.set_message_processor(|id, body| {
+        let mut records = InMemoryTransport::default_message_processor(id, body);
+        // message expired code
+        records[1].1["compute"]["exit_code"] = json!(57);
+        records
+    });
+    let client = create_client(&transport, TimeoutsConfig {
+        message_retries_count: 1,
+        ..TimeoutsConfig::default()
+    });
+    let (observer, events) = processing_events();
+
+    Contract::call_json_with_events(
+        &client,
+        MsgAddressInt::from_str(ADDRESS).unwrap(),
+        transfer_call_set(),
+        None,
+        Some(observer.as_ref())).await.unwrap_err();
+    drop(observer);
+
+    let events: Vec<ProcessingEvent> = events.collect().await;
+    let kinds: Vec<&str> = events
+        .iter()
+        .map(|event| match event {
+            ProcessingEvent::MessageSent { .. } => "sent",
+            ProcessingEvent::ShardBlockFound { .. } => "shard block",
+            ProcessingEvent::TransactionFound { .. } => "transaction",
+            ProcessingEvent::Expired { .. } => "expired",
+            ProcessingEvent::Retrying { .. } => "retrying",
+        })
+        .collect();
+    assert_eq!(kinds, vec![
+        "sent", "shard block", "expired", "retrying",
+        "sent", "shard block", "expired",
+    ]);
+    assert_eq!(events[3], ProcessingEvent::Retrying { try_index: 1 });
+}
+
+#[tokio::main]
+#[test]
+async fn test_processing_resume() {
+    let transport = InMemoryTransport::new();
+    // transaction is not produced until the processing is resumed
+    transport.set_message_processor(|_, _| Vec::new());
+    let client = create_client(&transport, TimeoutsConfig::default());
+
+    let mut processing = MessageProcessing::new("01".into(), vec![1, 2, 3], None, 0);
+    processing.next_event(&client).await.unwrap();
+    let state = serde_json::to_value(processing.state()).unwrap();
+    drop(processing);
+
+    assert_eq!(state["messageId"], "01");
+    assert_eq!(state["messageBodyBase64"], "AQID");
+    assert_eq!(state["sent"], true);
+    assert!(state["startTime"].is_number());
+
+    for (table, record) in InMemoryTransport::default_message_processor("01", &[1, 2, 3]) {
+        transport.add_record(&table, record);
+    }
+
+    let state: ProcessingState = serde_json::from_value(state).unwrap();
+    let transaction = MessageProcessing::resume(state).run(&client, None).await.unwrap();
+    assert_eq!(transaction.in_message_id().unwrap().to_string(), "01");
+    // message is not sent again
+    assert_eq!(transport.sent_messages().len(), 1);
+}
+
+#[tokio::main]
+#[test]
+async fn test_processing_resume_expired() {
+    let transport = InMemoryTransport::new();
+    let client = create_client(&transport, TimeoutsConfig::default());
+
+    // message expired while the processing was interrupted: master block generated after
+    // the expiration guarantees that the message won't be processed
+    let expire = client.now().unwrap() - 10;
+    transport.add_record("transactions", json!({ "id": "t1" }));
+    transport.add_record("blocks", json!({
+        "id": "b1",
+        "master": { "min_shard_gen_utime": expire + 1 },
+        "in_msg_descr": [{ "transaction_id": "t1" }],
+    }));
+
+    let mut processing = MessageProcessing::resume(ProcessingState {
+        message_id: "01".to_owned(),
+        message: vec![1, 2, 3],
+        expire: Some(expire),
+        try_index: 0,
+        sent: true,
+        start_time: Some(expire - 10),
+    });
+    assert_eq!(
+        processing.next_event(&client).await.unwrap(),
+        ProcessingEvent::Expired { message_id: "01".to_owned(), try_index: 0 });
+    assert!(processing.is_finished());
+    assert!(transport.sent_messages().is_empty());
+}
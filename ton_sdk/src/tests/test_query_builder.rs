@@ -0,0 +1,74 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use super::*;
+
+#[test]
+fn test_filter() {
+    let filter = Filter::<Transactions>::new()
+        .eq(TransactionField::InMsg, "1234")
+        .ge(TransactionField::Now, 10)
+        .le(TransactionField::Now, 20)
+        .eq(TransactionField::ComputeExitCode, 0);
+
+    assert_eq!(filter.to_value(), json!({
+        "in_msg": { "eq": "1234" },
+        "now": { "ge": 10, "le": 20 },
+        "compute": { "exit_code": { "eq": 0 } }
+    }));
+}
+
+#[test]
+fn test_filter_arrays_and_or() {
+    let filter = Filter::<Blocks>::new()
+        .eq(BlockField::InMsgDescrTransactionId, "1")
+        .or(Filter::new().in_list(BlockField::SeqNo, vec![1, 2]))
+        .or(Filter::new().ne(BlockField::Status, 2));
+
+    assert_eq!(filter.to_value(), json!({
+        "in_msg_descr": { "any": { "transaction_id": { "eq": "1" } } },
+        "OR": {
+            "seq_no": { "in": [1, 2] },
+            "OR": {
+                "status": { "ne": 2 }
+            }
+        }
+    }));
+
+    let filter = Filter::<Transactions>::new().eq(TransactionField::OutMsgs, "2");
+    assert_eq!(filter.to_value(), json!({ "out_msgs": { "any": { "eq": "2" } } }));
+}
+
+#[test]
+fn test_fields() {
+    let fields = Fields::<Transactions>::new(&[
+        TransactionField::Id,
+        TransactionField::ComputeExitCode,
+        TransactionField::Now,
+        TransactionField::ComputeSuccess,
+    ]).with(TransactionField::StorageStatusChange);
+
+    assert_eq!(
+        fields.to_string(),
+        "id compute { exit_code success } now storage { status_change }");
+}
+
+#[test]
+fn test_filter_type() {
+    assert_eq!(filter_type("accounts").unwrap(), "AccountFilter");
+    assert_eq!(filter_type("transactions").unwrap(), "TransactionFilter");
+    assert_eq!(filter_type("blocks_signatures").unwrap(), "BlockSignaturesFilter");
+    assert_eq!(filter_type("shards").unwrap(), "ShardFilter");
+    assert!(filter_type("").is_err());
+}
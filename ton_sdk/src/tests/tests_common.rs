@@ -381,7 +381,9 @@ pub async fn contract_call_local(
         .expect("Error unwrap result while loading Contract");
 
     // call needed method
-    let messages = contract.local_call_tvm_json(func.to_owned(), None, input.to_owned(), abi.to_owned(), key_pair)
+    let message = contract.create_call_message_json(func.to_owned(), None, input.to_owned(), abi.to_owned(), key_pair)
+        .expect("Error creating message");
+    let messages = contract.local_call_tvm_with_clock(message, client)
         .expect("Error calling locally");
 
     for msg in messages {
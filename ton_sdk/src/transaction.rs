@@ -17,7 +17,7 @@ use crate::json_helper;
 use crate::{Message, MessageId};
 use crate::types::StringId;
 use crate::node_client::NodeClient;
-use crate::types::TRANSACTIONS_TABLE_NAME;
+use crate::query_builder::{Fields, TransactionField, Transactions};
 use ton_types::Result;
 
 use futures::{Stream, StreamExt};
@@ -73,10 +73,9 @@ impl Transaction {
 
     // Asynchronously loads a Transaction instance or None if transaction with given id is not exists
     pub async fn load<'a>(client: &'a NodeClient, id: &TransactionId) -> Result<Option<Transaction>> {
-        let value = client.load_record_fields(
-            TRANSACTIONS_TABLE_NAME,
+        let value = client.load_record_fields_typed(
             &id.to_string(),
-            TRANSACTION_FIELDS_ORDINARY).await?;
+            &transaction_fields_ordinary()).await?;
 
         if value == serde_json::Value::Null {
             Ok(None)
@@ -122,30 +121,34 @@ impl Transaction {
         self.aborted
     }
 
-    // Asynchronously loads an instances of transaction's out messages
+    // Asynchronously loads an instances of transaction's out messages. All messages are
+    // requested at once
     pub fn load_out_messages<'a>(&self, client: &'a NodeClient) -> Result<impl Stream<Item = Result<Message>> + Send + 'a> {
-        Ok(futures::stream::iter(self.out_messages_id().clone()).then(move |id| async move { 
-            match Message::load(client, &id).await {
-                Err(err) => Err(err),
-                Ok(msg) => msg.ok_or(SdkError::NoData.into())
-            }}))
+        let ids = self.out_messages_id().clone();
+        Ok(futures::stream::once(async move { Message::load_many(client, &ids).await })
+            .map(|result| futures::stream::iter(match result {
+                Err(err) => vec![Err(err)],
+                Ok(messages) => messages
+                    .into_iter()
+                    .map(|msg| msg.ok_or(SdkError::NoData.into()))
+                    .collect()
+            }))
+            .flatten())
     }
 }
 
-pub const TRANSACTION_FIELDS_ORDINARY: &str = r#"
-    id
-    aborted
-    compute {
-        compute_type
-        skipped_reason
-        exit_code
-        success
-    }
-    storage {
-       status_change 
-    }
-    in_msg
-    now
-    out_msgs
-    status
-"#;
+pub fn transaction_fields_ordinary() -> Fields<Transactions> {
+    Fields::new(&[
+        TransactionField::Id,
+        TransactionField::Aborted,
+        TransactionField::ComputeType,
+        TransactionField::ComputeSkippedReason,
+        TransactionField::ComputeExitCode,
+        TransactionField::ComputeSuccess,
+        TransactionField::StorageStatusChange,
+        TransactionField::InMsg,
+        TransactionField::Now,
+        TransactionField::OutMsgs,
+        TransactionField::Status,
+    ])
+}
@@ -0,0 +1,297 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.  You may obtain a copy of the
+* License at: https://ton.dev/licenses
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use crate::error::SdkError;
+use crate::node_client::{OrderBy, SortDirection};
+use crate::query_builder::{self, BlockField};
+use crate::types::{ServerInfo, BLOCKS_TABLE_NAME};
+use futures::{Future, Stream, StreamExt};
+use graphite::client::GqlClient;
+use graphite::types::{GraphiteError, SubscriptionEvent, VariableRequest};
+use serde_json::Value;
+use std::pin::Pin;
+use ton_types::{error, Result};
+
+pub type TransportFuture<'a, T> = Pin<Box<dyn Future<Output=Result<T>> + Send + 'a>>;
+pub type RecordStream = Pin<Box<dyn Stream<Item=Result<Value>> + Send>>;
+
+// Parameters of a database query
+pub struct QueryParams {
+    pub table: String,
+    pub filter: Value,
+    pub fields: String,
+    pub order_by: Option<OrderBy>,
+    pub limit: Option<u32>,
+    // Time in ms to wait for at least one record matching the filter
+    pub timeout: Option<u32>,
+}
+
+// Access to the node database used by `NodeClient`
+pub trait Transport: Send + Sync {
+    // Returns array of records matching the query
+    fn query<'a>(&'a self, params: &'a QueryParams) -> TransportFuture<'a, Value>;
+
+    // Executes several queries at once. Results are returned in the same order as queries
+    fn query_batch<'a>(&'a self, params: &'a [QueryParams]) -> TransportFuture<'a, Vec<Result<Value>>>;
+
+    // Returns stream of created or updated records matching the filter
+    fn subscribe<'a>(&'a self, table: &'a str, filter: &'a Value, fields: &'a str)
+        -> TransportFuture<'a, RecordStream>;
+
+    // Posts serialized message to the node
+    fn send_message<'a>(&'a self, id: &'a [u8], body: &'a [u8]) -> TransportFuture<'a, ()>;
+
+    // Returns server version, schema features and network identity
+    fn server_info<'a>(&'a self) -> TransportFuture<'a, ServerInfo>;
+
+    // Returns current server time in ms
+    fn server_time<'a>(&'a self) -> TransportFuture<'a, i64>;
+}
+
+const INFO_QUERY: &str = "query info { info { version } }";
+const TIME_QUERY: &str = "query info { info { time } }";
+const SCHEMA_QUERY: &str = "query schema { __schema { queryType { fields { name } } } }";
+
+// Query of the first masterchain block. It keeps the network global id and refers
+// to the zerostate as its previous block
+pub(crate) fn network_block_query() -> QueryParams {
+    QueryParams {
+        table: BLOCKS_TABLE_NAME.to_owned(),
+        filter: json!({ "workchain_id": { "eq": -1 }, "seq_no": { "eq": 1 } }),
+        fields: "global_id prev_ref { root_hash }".to_owned(),
+        order_by: None,
+        limit: Some(1),
+        timeout: None,
+    }
+}
+
+// Query of the last masterchain block, its generation time is used as server time
+// by servers without the `time` field of `info`
+fn last_block_query() -> QueryParams {
+    QueryParams {
+        table: BLOCKS_TABLE_NAME.to_owned(),
+        filter: json!({ "workchain_id": { "eq": -1 } }),
+        fields: "gen_utime".to_owned(),
+        order_by: Some(OrderBy::new(BlockField::SeqNo, SortDirection::Descending)),
+        limit: Some(1),
+        timeout: None,
+    }
+}
+
+// Extracts global id and zerostate hash from the `network_block_query` result
+pub(crate) fn network_identity(blocks: &Value) -> (Option<i32>, Option<String>) {
+    let block = &blocks[0];
+    (
+        block["global_id"].as_i64().map(|id| id as i32),
+        block["prev_ref"]["root_hash"].as_str().map(|hash| hash.to_owned())
+    )
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MutationRequest {
+    pub id: String,
+    pub body: String
+}
+
+fn generate_query_var(params: &QueryParams) -> Result<VariableRequest> {
+    let mut query = format!(
+        r#"query {table}
+        ($filter: {scheme_type}, $orderBy: [QueryOrderBy], $limit: Int, $timeout: Float)
+        {{
+            {table}(filter: $filter, orderBy: $orderBy, limit: $limit, timeout: $timeout)
+            {{ {fields} }}
+        }}"#,
+        table=params.table,
+        scheme_type=query_builder::filter_type(&params.table)?,
+        fields=params.fields
+    );
+    query = query.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    let variables = json!({
+        "filter" : params.filter,
+        "orderBy": params.order_by,
+        "limit": params.limit,
+        "timeout": params.timeout
+    });
+
+    Ok(VariableRequest::new(query, Some(variables)))
+}
+
+fn generate_subscription(table: &str, filter: &Value, fields: &str) -> Result<VariableRequest> {
+    let query = format!("subscription {table}($filter: {type}) {{ {table}(filter: $filter) {{ {fields} }} }}",
+        type=query_builder::filter_type(table)?,
+        table=table,
+        fields=fields);
+    let query = query.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    let variables = json!({
+        "filter" : filter
+    });
+
+    Ok(VariableRequest::new(query, Some(variables)))
+}
+
+fn generate_post_mutation(requests: &[MutationRequest]) -> Result<VariableRequest> {
+    let query = "mutation postRequests($requests:[Request]){postRequests(requests:$requests)}".to_owned();
+    let variables = json!({
+        "requests": serde_json::to_value(requests)?
+    });
+
+    Ok(VariableRequest::new(query, Some(variables)))
+}
+
+// Keeps server errors list available to the caller
+fn graphql_error(err: GraphiteError) -> failure::Error {
+    SdkError::Graphql { err }.into()
+}
+
+// Older servers reject queries they don't know with GraphQL errors, such answers mean
+// the feature is unsupported. Network errors are passed to the caller
+fn optional_answer(result: std::result::Result<Value, GraphiteError>) -> Result<Option<Value>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if !err.errors().is_empty() => Ok(None),
+        Err(err) => Err(graphql_error(err))
+    }
+}
+
+// Extracts records array from the query answer
+fn extract_records(table: &str, result: Value) -> Result<Value> {
+    let records_array = &result["data"][table];
+    if records_array.is_null() {
+        Err(SdkError::InvalidData { msg: format!("Invalid query answer: {}", result) }.into())
+    } else {
+        Ok(records_array.clone())
+    }
+}
+
+impl Transport for GqlClient {
+    fn query<'a>(&'a self, params: &'a QueryParams) -> TransportFuture<'a, Value> {
+        Box::pin(async move {
+            let result = self.query_vars(generate_query_var(params)?).await.map_err(graphql_error)?;
+            extract_records(&params.table, result)
+        })
+    }
+
+    fn query_batch<'a>(&'a self, params: &'a [QueryParams]) -> TransportFuture<'a, Vec<Result<Value>>> {
+        Box::pin(async move {
+            let requests = params
+                .iter()
+                .map(generate_query_var)
+                .collect::<Result<Vec<VariableRequest>>>()?;
+
+            let results = self.query_vars_batch(&requests).await.map_err(graphql_error)?;
+
+            Ok(results
+                .into_iter()
+                .zip(params.iter())
+                .map(|(result, params)| extract_records(&params.table, result.map_err(graphql_error)?))
+                .collect())
+        })
+    }
+
+    fn subscribe<'a>(&'a self, table: &'a str, filter: &'a Value, fields: &'a str)
+        -> TransportFuture<'a, RecordStream>
+    {
+        Box::pin(async move {
+            let request = generate_subscription(table, filter, fields)?;
+
+            let closure_table = table.to_owned();
+
+            let stream = self.subscribe(request).await.map_err(graphql_error)?
+                .map(move |result| {
+                        match result {
+                            Err(err) => Err(graphql_error(err)),
+                            // notify caller that updates could be missed while connection was lost
+                            Ok(SubscriptionEvent::Reconnected) =>
+                                Err(SdkError::SubscriptionReconnected.into()),
+                            Ok(SubscriptionEvent::Data(value)) => {
+                                // try to extract the record value from the answer
+                                let record_value = &value["payload"]["data"][&closure_table];
+
+                                if record_value.is_null() {
+                                    Err(error!(SdkError::InvalidData {
+                                        msg: format!("Invalid subscription answer: {}", value)
+                                    }).into())
+                                } else {
+                                    Ok(record_value.clone())
+                                }
+                            }
+                        }
+                    }
+                );
+
+            Ok(Box::pin(stream) as RecordStream)
+        })
+    }
+
+    fn send_message<'a>(&'a self, id: &'a [u8], body: &'a [u8]) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            let request = MutationRequest {
+                id: base64::encode(id),
+                body: base64::encode(body)
+            };
+
+            self.query_vars(generate_post_mutation(&[request])?).await.map_err(graphql_error)?;
+            Ok(())
+        })
+    }
+
+    fn server_info<'a>(&'a self) -> TransportFuture<'a, ServerInfo> {
+        Box::pin(async move {
+            let requests = [
+                VariableRequest::new(INFO_QUERY.to_owned(), None),
+                VariableRequest::new(SCHEMA_QUERY.to_owned(), None),
+                generate_query_var(&network_block_query())?,
+            ];
+            let mut results = self.query_vars_batch(&requests).await.map_err(graphql_error)?.into_iter();
+            let mut next = || optional_answer(results.next().unwrap_or(Ok(Value::Null)));
+
+            let version = next()?
+                .and_then(|info| info["data"]["info"]["version"].as_str().map(|version| version.to_owned()));
+
+            let features = next()?
+                .and_then(|schema| schema["data"]["__schema"]["queryType"]["fields"].as_array().cloned())
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|field| field["name"].as_str().map(|name| name.to_owned()))
+                .collect();
+
+            let (global_id, zerostate_hash) = match next()? {
+                Some(blocks) => network_identity(&extract_records(BLOCKS_TABLE_NAME, blocks)?),
+                None => (None, None)
+            };
+
+            Ok(ServerInfo { version, features, global_id, zerostate_hash })
+        })
+    }
+
+    fn server_time<'a>(&'a self) -> TransportFuture<'a, i64> {
+        Box::pin(async move {
+            let answer = optional_answer(self.query_vars(VariableRequest::new(TIME_QUERY.to_owned(), None)).await)?;
+            if let Some(time) = answer.as_ref().and_then(|answer| answer["data"]["info"]["time"].as_f64()) {
+                return Ok(time as i64);
+            }
+
+            // block generation time lags behind the server time by the block interval
+            let params = last_block_query();
+            let result = self.query_vars(generate_query_var(&params)?).await.map_err(graphql_error)?;
+            let blocks = extract_records(&params.table, result)?;
+            match blocks[0]["gen_utime"].as_i64() {
+                Some(time) => Ok(time * 1000),
+                None => Err(SdkError::InvalidData { msg: "Server time is unavailable".to_owned() }.into())
+            }
+        })
+    }
+}
@@ -25,6 +25,10 @@ pub const DEFAULT_EXPIRATION_TIMEOUT: u32 = 10000;
 pub const DEFAULT_PROCESSING_TIMEOUT: u32 = 40000;
 pub const DEFAULT_TIMEOUT_GROW_FACTOR: f32 = 1.5;
 pub const DEFAULT_WAIT_TIMEOUT: u32 = 40000;
+pub const DEFAULT_NETWORK_ATTEMPTS: u32 = 3;
+pub const DEFAULT_NETWORK_RETRY_DELAY: u32 = 500;
+pub const DEFAULT_NETWORK_RETRY_MAX_DELAY: u32 = 10000;
+pub const DEFAULT_NETWORK_RETRY_GROW_FACTOR: f32 = 2.0;
 
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -51,13 +55,85 @@ impl Default for TimeoutsConfig {
     }
 }
 
+// Retries of requests failed because of network errors or unavailable servers
+#[cfg(feature = "node_interaction")]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    // Total number of attempts, 1 disables retries
+    pub max_attempts: u32,
+    // Delay in ms before the first retry
+    pub delay: u32,
+    pub delay_grow_factor: f32,
+    pub max_delay: u32,
+    // HTTP statuses which are retried in addition to network errors
+    pub retryable_statuses: Vec<u16>,
+}
+
+#[cfg(feature = "node_interaction")]
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_NETWORK_ATTEMPTS,
+            delay: DEFAULT_NETWORK_RETRY_DELAY,
+            delay_grow_factor: DEFAULT_NETWORK_RETRY_GROW_FACTOR,
+            max_delay: DEFAULT_NETWORK_RETRY_MAX_DELAY,
+            retryable_statuses: vec![429, 502, 503, 504],
+        }
+    }
+}
+
+// Version, schema and network identity reported by the GraphQL server
+#[cfg(feature = "node_interaction")]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ServerInfo {
+    // Server version like `0.27.1`, `None` for servers without the `info` query
+    pub version: Option<String>,
+    // Root query fields of the server schema
+    pub features: Vec<String>,
+    // Global id of the network taken from the first masterchain block
+    pub global_id: Option<i32>,
+    // Hex encoded hash of the masterchain zerostate, unique for every network
+    pub zerostate_hash: Option<String>,
+}
+
+#[cfg(feature = "node_interaction")]
+impl ServerInfo {
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|name| name == feature)
+    }
+
+    // Version packed into one number, e.g. `0.27.1` is `27001`
+    pub fn version_number(&self) -> Option<u32> {
+        let mut parts = self.version.as_ref()?.split('.').map(|part| part.parse::<u32>().ok());
+        let major = parts.next()??;
+        let minor = parts.next().unwrap_or(Some(0))?;
+        let patch = parts.next().unwrap_or(Some(0))?;
+        Some(major * 1_000_000 + minor * 1_000 + patch)
+    }
+}
+
 // Represents config to connect node
 #[cfg(feature = "node_interaction")]
 #[derive(Debug, Deserialize, Serialize)]
 pub struct NodeClientConfig {
     pub base_url: Option<String>,
+    // Additional servers used when `base_url` is unreachable
+    pub endpoints: Option<Vec<String>>,
     pub timeouts: Option<TimeoutsConfig>,
     pub access_key: Option<String>,
+    pub retry: Option<RetryConfig>,
+    // Messages are sent only if the server reports this zerostate hash
+    pub expected_zerostate_hash: Option<String>,
+    // Clock offset in ms beyond which `NodeClient::sync_clock` fails. The offset is
+    // applied to messages expiration time regardless of the limit
+    pub max_clock_offset: Option<u32>,
+    // Number of finalized records (messages, transactions and blocks) kept in memory
+    // to be loaded by id without requests to the server. Caching is off if not set
+    pub records_cache_size: Option<usize>,
+    // Transactions of processed messages are awaited through subscription instead of
+    // long polling queries. Polling is used if the subscription fails
+    pub wait_by_subscription: Option<bool>,
 }
 
 #[cfg(not(feature = "node_interaction"))]